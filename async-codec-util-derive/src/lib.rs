@@ -0,0 +1,633 @@
+//! Derive macros for the traits from the
+//! [async-codec](https://crates.io/crates/async-codec) crate.
+//!
+//! Use these via the `derive` feature of async-codec-util, whose `field` module documents the
+//! supported attributes and the generated types.
+#![deny(missing_docs)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, TokenStream as TokenStream2};
+use syn::{Attribute, Data, DataEnum, DeriveInput, Error, Expr, ExprLit, Fields, Ident, Lit,
+          LitStr, Type};
+use syn::spanned::Spanned;
+
+/// Derive an encoder for a struct or enum, see `async_codec_util::field`.
+#[proc_macro_derive(AsyncEncode, attributes(codec))]
+pub fn derive_async_encode(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand_encode(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Derive a decoder for a struct or enum, see `async_codec_util::field`.
+#[proc_macro_derive(AsyncDecode, attributes(codec))]
+pub fn derive_async_decode(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand_decode(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+// Path of the runtime support module.
+fn runtime() -> TokenStream2 {
+    quote!(::async_codec_util::field)
+}
+
+// Path of the reexported traits the generated code relies on.
+fn private() -> TokenStream2 {
+    quote!(::async_codec_util::field::__private)
+}
+
+struct Field {
+    binding: Ident,
+    ty: Type,
+    format: TokenStream2,
+}
+
+fn parse_fields(fields: &Fields) -> Result<Vec<Field>, Error> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+                 Ok(Field {
+                        binding: format_ident!("__field{}", i),
+                        ty: field.ty.clone(),
+                        format: field_format(&field.attrs)?,
+                    })
+             })
+        .collect()
+}
+
+fn parse_len(lit: &LitStr) -> Result<Ident, Error> {
+    match lit.value().as_str() {
+        "u8" | "u16" | "u32" | "u64" => Ok(Ident::new(&lit.value(), lit.span())),
+        _ => Err(Error::new(lit.span(), "expected \"u8\", \"u16\", \"u32\" or \"u64\"")),
+    }
+}
+
+// The format of a field, selected by its `#[codec(...)]` attribute.
+fn field_format(attrs: &[Attribute]) -> Result<TokenStream2, Error> {
+    let rt = runtime();
+    let mut format = quote!(#rt::Auto);
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("codec")) {
+        attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("be") {
+                    format = quote!(#rt::Be);
+                } else if meta.path.is_ident("le") {
+                    format = quote!(#rt::Le);
+                } else if meta.path.is_ident("varint") {
+                    format = quote!(#rt::Varint);
+                } else if meta.path.is_ident("len_prefix") {
+                    let len = parse_len(&meta.value()?.parse()?)?;
+                    format = quote!(#rt::LenPrefix<::std::primitive::#len>);
+                } else {
+                    return Err(meta.error("unsupported field format"));
+                }
+                Ok(())
+            })?;
+    }
+
+    Ok(format)
+}
+
+// The type and format of the discriminant of an enum, selected by `#[codec(tag = "...")]`.
+fn tag_format(attrs: &[Attribute]) -> Result<(TokenStream2, TokenStream2), Error> {
+    let rt = runtime();
+    let mut tag = (quote!(::std::primitive::u64), quote!(#rt::Varint));
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("codec")) {
+        attr.parse_nested_meta(|meta| {
+                if !meta.path.is_ident("tag") {
+                    return Err(meta.error("unsupported enum attribute"));
+                }
+
+                let lit: LitStr = meta.value()?.parse()?;
+                if lit.value() == "varint" {
+                    tag = (quote!(::std::primitive::u64), quote!(#rt::Varint));
+                } else {
+                    let ty = parse_len(&lit)?;
+                    let format = if ty == "u8" {
+                        quote!(#rt::Auto)
+                    } else {
+                        quote!(#rt::Be)
+                    };
+                    tag = (quote!(::std::primitive::#ty), format);
+                }
+                Ok(())
+            })?;
+    }
+
+    Ok(tag)
+}
+
+// The discriminant of each variant, counting up from the last explicit one like rustc does.
+fn discriminants(data: &DataEnum) -> Result<Vec<Literal>, Error> {
+    let mut next = 0u64;
+    let mut discs = Vec::with_capacity(data.variants.len());
+
+    for variant in data.variants.iter() {
+        if let Some((_, ref expr)) = variant.discriminant {
+            next = match *expr {
+                Expr::Lit(ExprLit { lit: Lit::Int(ref int), .. }) => int.base10_parse()?,
+                _ => {
+                    return Err(Error::new(expr.span(),
+                                          "discriminants must be unsigned integer literals"))
+                }
+            };
+        }
+        discs.push(Literal::u64_unsuffixed(next));
+        next = next.wrapping_add(1);
+    }
+
+    Ok(discs)
+}
+
+// A pattern binding (or an expression consisting of) all fields.
+fn pattern(path: TokenStream2, fields: &Fields, bound: &[Field]) -> TokenStream2 {
+    let bindings = bound.iter().map(|field| &field.binding);
+    match *fields {
+        Fields::Named(ref named) => {
+            let names = named.named.iter().map(|field| field.ident.as_ref().unwrap());
+            quote!(#path { #(#names: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => path,
+    }
+}
+
+fn check_input(input: &DeriveInput) -> Result<(), Error> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(input.generics.span(), "generic types are not supported"));
+    }
+
+    match input.data {
+        Data::Struct(_) => Ok(()),
+        Data::Enum(ref data) if data.variants.is_empty() => {
+            Err(Error::new(input.ident.span(), "enums without variants are not supported"))
+        }
+        Data::Enum(_) => Ok(()),
+        Data::Union(_) => Err(Error::new(input.ident.span(), "unions are not supported")),
+    }
+}
+
+fn idents(prefix: &str, n: usize) -> Vec<Ident> {
+    (0..n).map(|i| format_ident!("{}{}", prefix, i)).collect()
+}
+
+// An encoder running the given encoders in sequence.
+fn fields_encoder(head: TokenStream2, name: &Ident, types: &[TokenStream2]) -> TokenStream2 {
+    let p = private();
+    let n = types.len();
+    let encs = idents("__enc", n);
+
+    let poll = if n == 0 {
+        quote! {
+            fn poll_encode<W: #p::AsyncWrite>(self,
+                                             _: &mut #p::Context,
+                                             _: &mut W)
+                                             -> #p::PollEnc<Self> {
+                #p::PollEnc::Done(0)
+            }
+        }
+    } else {
+        let arms = encs.iter().enumerate().map(|(i, enc)| {
+            quote! {
+                #i => {
+                    let enc = self.#enc.take().expect("Polled encoder after completion");
+                    match #p::AsyncEncode::poll_encode(enc, cx, writer) {
+                        #p::PollEnc::Done(written) => {
+                            self.__state += 1;
+                            if self.__state == #n {
+                                #p::PollEnc::Done(written)
+                            } else {
+                                #p::PollEnc::Progress(self, written)
+                            }
+                        }
+                        #p::PollEnc::Progress(enc, written) => {
+                            self.#enc = ::std::option::Option::Some(enc);
+                            #p::PollEnc::Progress(self, written)
+                        }
+                        #p::PollEnc::Pending(enc) => {
+                            self.#enc = ::std::option::Option::Some(enc);
+                            #p::PollEnc::Pending(self)
+                        }
+                        #p::PollEnc::Errored(err) => #p::PollEnc::Errored(err),
+                    }
+                }
+            }
+        });
+
+        quote! {
+            fn poll_encode<W: #p::AsyncWrite>(mut self,
+                                             cx: &mut #p::Context,
+                                             writer: &mut W)
+                                             -> #p::PollEnc<Self> {
+                match self.__state {
+                    #(#arms)*
+                    _ => panic!("Polled encoder after completion"),
+                }
+            }
+        }
+    };
+
+    quote! {
+        #[allow(dead_code)]
+        #head struct #name {
+            __state: usize,
+            #(#encs: ::std::option::Option<#types>,)*
+        }
+
+        impl #p::AsyncEncode for #name {
+            #poll
+        }
+
+        impl #p::AsyncEncodeLen for #name {
+            fn remaining_bytes(&self) -> usize {
+                0 #(+ self.#encs.as_ref().map_or(0, #p::AsyncEncodeLen::remaining_bytes))*
+            }
+        }
+    }
+}
+
+fn encoder_type(field: &Field) -> TokenStream2 {
+    let rt = runtime();
+    let (ty, format) = (&field.ty, &field.format);
+    quote!(<#ty as #rt::EncodeField<#format>>::Encoder)
+}
+
+fn encoder_init(field: &Field) -> TokenStream2 {
+    let rt = runtime();
+    let (ty, format, binding) = (&field.ty, &field.format, &field.binding);
+    quote!(<#ty as #rt::EncodeField<#format>>::field_encoder(#binding)?)
+}
+
+fn expand_encode(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    check_input(input)?;
+    let rt = runtime();
+    let p = private();
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let name = format_ident!("{}Encoder", ident);
+    let doc = format!("Encoder for `{}`, created via `async_codec_util::field::encoder`.",
+                      ident);
+
+    match input.data {
+        Data::Struct(ref data) => {
+            let fields = parse_fields(&data.fields)?;
+            let types: Vec<_> = fields.iter().map(encoder_type).collect();
+            let inits = fields.iter().map(encoder_init);
+            let encs = idents("__enc", fields.len());
+            let pat = pattern(quote!(#ident), &data.fields, &fields);
+            let def = fields_encoder(quote!(#[doc = #doc] #vis), &name, &types);
+
+            Ok(quote! {
+                #def
+
+                impl #rt::EncodeField<#rt::Auto> for #ident {
+                    type Encoder = #name;
+
+                    fn field_encoder(self)
+                                     -> ::std::result::Result<#name, #rt::PrefixOverflow> {
+                        let #pat = self;
+                        ::std::result::Result::Ok(#name {
+                            __state: 0,
+                            #(#encs: ::std::option::Option::Some(#inits),)*
+                        })
+                    }
+                }
+            })
+        }
+        Data::Enum(ref data) => {
+            let (tag_ty, tag_format) = tag_format(&input.attrs)?;
+            let state = format_ident!("__{}EncoderState", ident);
+            let mut defs = Vec::new();
+            let mut variants = Vec::new();
+            let mut news = Vec::new();
+            let mut polls = Vec::new();
+            let mut lens = Vec::new();
+
+            for (variant, disc) in data.variants.iter().zip(discriminants(data)?) {
+                let v = &variant.ident;
+                let fields = parse_fields(&variant.fields)?;
+                let hidden = format_ident!("__{}Encoder{}", ident, v);
+
+                let mut types = vec![quote!(<#tag_ty as #rt::EncodeField<#tag_format>>::Encoder)];
+                types.extend(fields.iter().map(encoder_type));
+                let mut inits =
+                    vec![quote!(<#tag_ty as #rt::EncodeField<#tag_format>>::field_encoder(#disc)?)];
+                inits.extend(fields.iter().map(encoder_init));
+                let encs = idents("__enc", types.len());
+                let pat = pattern(quote!(#ident::#v), &variant.fields, &fields);
+
+                defs.push(fields_encoder(quote!(), &hidden, &types));
+                variants.push(quote!(#v(#hidden),));
+                news.push(quote! {
+                    #pat => #name(#state::#v(#hidden {
+                        __state: 0,
+                        #(#encs: ::std::option::Option::Some(#inits),)*
+                    })),
+                });
+                polls.push(quote! {
+                    #state::#v(enc) => {
+                        match #p::AsyncEncode::poll_encode(enc, cx, writer) {
+                            #p::PollEnc::Done(written) => #p::PollEnc::Done(written),
+                            #p::PollEnc::Progress(enc, written) => {
+                                #p::PollEnc::Progress(#name(#state::#v(enc)), written)
+                            }
+                            #p::PollEnc::Pending(enc) => {
+                                #p::PollEnc::Pending(#name(#state::#v(enc)))
+                            }
+                            #p::PollEnc::Errored(err) => #p::PollEnc::Errored(err),
+                        }
+                    }
+                });
+                lens.push(quote!(#state::#v(ref enc) => #p::AsyncEncodeLen::remaining_bytes(enc),));
+            }
+
+            Ok(quote! {
+                #(#defs)*
+
+                enum #state {
+                    #(#variants)*
+                }
+
+                #[doc = #doc]
+                #vis struct #name(#state);
+
+                impl #p::AsyncEncode for #name {
+                    fn poll_encode<W: #p::AsyncWrite>(self,
+                                                     cx: &mut #p::Context,
+                                                     writer: &mut W)
+                                                     -> #p::PollEnc<Self> {
+                        match self.0 {
+                            #(#polls)*
+                        }
+                    }
+                }
+
+                impl #p::AsyncEncodeLen for #name {
+                    fn remaining_bytes(&self) -> usize {
+                        match self.0 {
+                            #(#lens)*
+                        }
+                    }
+                }
+
+                impl #rt::EncodeField<#rt::Auto> for #ident {
+                    type Encoder = #name;
+
+                    fn field_encoder(self)
+                                     -> ::std::result::Result<#name, #rt::PrefixOverflow> {
+                        ::std::result::Result::Ok(match self {
+                            #(#news)*
+                        })
+                    }
+                }
+            })
+        }
+        Data::Union(_) => unreachable!(),
+    }
+}
+
+// A decoder running the decoders of the given fields in sequence, and then evaluating `build`
+// with the decoded fields bound to their bindings.
+fn fields_decoder(head: TokenStream2,
+                  name: &Ident,
+                  item: &Ident,
+                  fields: &[Field],
+                  build: TokenStream2)
+                  -> TokenStream2 {
+    let rt = runtime();
+    let p = private();
+    let n = fields.len();
+    let decs = idents("__dec", n);
+    let items = idents("__item", n);
+    let tys: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    let formats: Vec<_> = fields.iter().map(|field| &field.format).collect();
+    let bindings = fields.iter().map(|field| &field.binding);
+
+    let poll = if n == 0 {
+        quote! {
+            fn poll_decode<R: #p::AsyncRead>(self,
+                                            _: &mut #p::Context,
+                                            _: &mut R)
+                                            -> #p::PollDec<#item, Self, #rt::FieldError> {
+                #p::PollDec::Done(#build, 0)
+            }
+        }
+    } else {
+        let arms = decs.iter().zip(items.iter()).enumerate().map(|(i, (dec, it))| {
+            quote! {
+                #i => {
+                    let dec = self.#dec.take().expect("Polled decoder after completion");
+                    match #p::AsyncDecode::poll_decode(dec, cx, reader) {
+                        #p::PollDec::Done(item, read) => {
+                            self.#it = ::std::option::Option::Some(item);
+                            self.__state += 1;
+                            if self.__state == #n {
+                                #p::PollDec::Done(self.__finish(), read)
+                            } else {
+                                #p::PollDec::Progress(self, read)
+                            }
+                        }
+                        #p::PollDec::Progress(dec, read) => {
+                            self.#dec = ::std::option::Option::Some(dec);
+                            #p::PollDec::Progress(self, read)
+                        }
+                        #p::PollDec::Pending(dec) => {
+                            self.#dec = ::std::option::Option::Some(dec);
+                            #p::PollDec::Pending(self)
+                        }
+                        #p::PollDec::Errored(err) => #p::PollDec::Errored(err),
+                    }
+                }
+            }
+        });
+
+        quote! {
+            fn poll_decode<R: #p::AsyncRead>(mut self,
+                                            cx: &mut #p::Context,
+                                            reader: &mut R)
+                                            -> #p::PollDec<#item, Self, #rt::FieldError> {
+                match self.__state {
+                    #(#arms)*
+                    _ => panic!("Polled decoder after completion"),
+                }
+            }
+        }
+    };
+
+    let finish = if n == 0 {
+        quote!()
+    } else {
+        quote! {
+            impl #name {
+                fn __finish(&mut self) -> #item {
+                    #(let #bindings = self.#items.take().unwrap();)*
+                    #build
+                }
+            }
+        }
+    };
+
+    quote! {
+        #[allow(dead_code)]
+        #head struct #name {
+            __state: usize,
+            #(#decs: ::std::option::Option<<#tys as #rt::DecodeField<#formats>>::Decoder>,)*
+            #(#items: ::std::option::Option<#tys>,)*
+        }
+
+        impl ::std::default::Default for #name {
+            fn default() -> #name {
+                #name {
+                    __state: 0,
+                    #(#decs: ::std::option::Option::Some(
+                        <#tys as #rt::DecodeField<#formats>>::field_decoder()),)*
+                    #(#items: ::std::option::Option::None,)*
+                }
+            }
+        }
+
+        #finish
+
+        impl #p::AsyncDecode for #name {
+            type Item = #item;
+            type Error = #rt::FieldError;
+
+            #poll
+        }
+    }
+}
+
+fn expand_decode(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    check_input(input)?;
+    let rt = runtime();
+    let p = private();
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let name = format_ident!("{}Decoder", ident);
+    let doc = format!("Decoder for `{}`, created via `async_codec_util::field::decoder`.",
+                      ident);
+
+    let def = match input.data {
+        Data::Struct(ref data) => {
+            let fields = parse_fields(&data.fields)?;
+            let build = pattern(quote!(#ident), &data.fields, &fields);
+            fields_decoder(quote!(#[doc = #doc] #vis), &name, ident, &fields, build)
+        }
+        Data::Enum(ref data) => {
+            let (tag_ty, tag_format) = tag_format(&input.attrs)?;
+            let state = format_ident!("__{}DecoderState", ident);
+            let mut defs = Vec::new();
+            let mut variants = Vec::new();
+            let mut tags = Vec::new();
+            let mut polls = Vec::new();
+
+            for (variant, disc) in data.variants.iter().zip(discriminants(data)?) {
+                let v = &variant.ident;
+                let fields = parse_fields(&variant.fields)?;
+                let hidden = format_ident!("__{}Decoder{}", ident, v);
+                let build = pattern(quote!(#ident::#v), &variant.fields, &fields);
+
+                defs.push(fields_decoder(quote!(), &hidden, ident, &fields, build));
+                variants.push(quote!(#v(#hidden),));
+                tags.push(quote! {
+                    #disc => {
+                        #p::PollDec::Progress(#name(#state::#v(::std::default::Default::default())),
+                                              read)
+                    }
+                });
+                polls.push(quote! {
+                    #state::#v(dec) => {
+                        match #p::AsyncDecode::poll_decode(dec, cx, reader) {
+                            #p::PollDec::Done(item, read) => #p::PollDec::Done(item, read),
+                            #p::PollDec::Progress(dec, read) => {
+                                #p::PollDec::Progress(#name(#state::#v(dec)), read)
+                            }
+                            #p::PollDec::Pending(dec) => {
+                                #p::PollDec::Pending(#name(#state::#v(dec)))
+                            }
+                            #p::PollDec::Errored(err) => #p::PollDec::Errored(err),
+                        }
+                    }
+                });
+            }
+
+            quote! {
+                #(#defs)*
+
+                enum #state {
+                    __Tag(<#tag_ty as #rt::DecodeField<#tag_format>>::Decoder),
+                    #(#variants)*
+                }
+
+                #[doc = #doc]
+                #vis struct #name(#state);
+
+                impl ::std::default::Default for #name {
+                    fn default() -> #name {
+                        #name(#state::__Tag(
+                            <#tag_ty as #rt::DecodeField<#tag_format>>::field_decoder()))
+                    }
+                }
+
+                impl #p::AsyncDecode for #name {
+                    type Item = #ident;
+                    type Error = #rt::FieldError;
+
+                    fn poll_decode<R: #p::AsyncRead>(self,
+                                                    cx: &mut #p::Context,
+                                                    reader: &mut R)
+                                                    -> #p::PollDec<#ident, Self, #rt::FieldError> {
+                        match self.0 {
+                            #state::__Tag(dec) => {
+                                match #p::AsyncDecode::poll_decode(dec, cx, reader) {
+                                    #p::PollDec::Done(tag, read) => {
+                                        match tag {
+                                            #(#tags)*
+                                            other => {
+                                                #p::PollDec::Errored(#p::DecodeError::DataError(
+                                                    #rt::FieldError::InvalidDiscriminant(
+                                                        other as u64)))
+                                            }
+                                        }
+                                    }
+                                    #p::PollDec::Progress(dec, read) => {
+                                        #p::PollDec::Progress(#name(#state::__Tag(dec)), read)
+                                    }
+                                    #p::PollDec::Pending(dec) => {
+                                        #p::PollDec::Pending(#name(#state::__Tag(dec)))
+                                    }
+                                    #p::PollDec::Errored(err) => #p::PollDec::Errored(err),
+                                }
+                            }
+                            #(#polls)*
+                        }
+                    }
+                }
+            }
+        }
+        Data::Union(_) => unreachable!(),
+    };
+
+    Ok(quote! {
+        #def
+
+        impl #rt::DecodeField<#rt::Auto> for #ident {
+            type Decoder = #name;
+
+            fn field_decoder() -> #name {
+                ::std::default::Default::default()
+            }
+        }
+    })
+}
@@ -0,0 +1,23 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_byteorder;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_byteorder::{decode_u8, decode_u32_be};
+use async_codec_util::decoder::{self, Aligned};
+use async_codec_util::sink::Position;
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        let position = Position::default();
+        let dec = decoder::chain(Aligned::new(decode_u8(), 8)
+                                     .tracking(position.clone())
+                                     .verify_padding(),
+                                 Aligned::new(decode_u32_be(), 512)
+                                     .tracking(position.clone())
+                                     .verify_padding());
+        decoder::tee(dec, position)
+    });
+});
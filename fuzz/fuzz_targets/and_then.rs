@@ -0,0 +1,17 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_byteorder;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_byteorder::{decode_u8, decode_u16_be};
+use async_codec_util::decoder::{self, AndThen};
+
+// A length-prefixed frame whose payload is skipped, followed by a tag byte.
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        AndThen::new(decode_u16_be(),
+                     |len| decoder::chain(decoder::skip(len as usize), decode_u8()))
+    });
+});
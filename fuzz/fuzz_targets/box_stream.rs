@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::{box_stream, decoder};
+
+const KEY: [u8; 32] = [7; 32];
+const NONCE: [u8; 24] = [9; 24];
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        box_stream::decode(decoder::until_delimiter(b"\n", 8192), &KEY, &NONCE)
+    });
+});
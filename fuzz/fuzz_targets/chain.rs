@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_byteorder;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_byteorder::{decode_u8, decode_u16_be, decode_u32_le};
+use async_codec_util::decoder;
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        decoder::chain(decoder::chain3(decode_u8(), decode_u16_be(), decode_u32_le()),
+                       decode_u8())
+    });
+});
@@ -0,0 +1,14 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::checksum::Crc32;
+use async_codec_util::decoder;
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        decoder::checksummed(decoder::until_delimiter(b"\n", 256), Crc32::new())
+    });
+});
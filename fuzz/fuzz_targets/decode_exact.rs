@@ -0,0 +1,11 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::decoder::{self, DecodeExact};
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || DecodeExact::new(decoder::until_delimiter(b"\n", 64), 16));
+});
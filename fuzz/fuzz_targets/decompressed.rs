@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::compression::DeflateDecompressor;
+use async_codec_util::decoder;
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        decoder::decompressed(decoder::until_delimiter(b"\n", 1024),
+                              DeflateDecompressor::new(false),
+                              4096)
+    });
+});
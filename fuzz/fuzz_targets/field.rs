@@ -0,0 +1,16 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::decoder;
+use async_codec_util::field::{DecodeField, LenPrefix, Varint};
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        decoder::chain3(<u64 as DecodeField<Varint>>::field_decoder(),
+                        <i32 as DecodeField<Varint>>::field_decoder(),
+                        <String as DecodeField<LenPrefix<u32>>>::field_decoder())
+    });
+});
@@ -0,0 +1,17 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::CodecError;
+use async_codec_util::decoder;
+
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        decoder::chain3(decoder::literal(b"MAGIC"),
+                        decoder::skip(4),
+                        decoder::skip_until(b"\r\n\r\n"))
+                .with_error::<CodecError>()
+    });
+});
@@ -0,0 +1,19 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::{decoder, MAX_IDLE_PROGRESS};
+
+// The first alternative may read more than `MAX_IDLE_PROGRESS` bytes before failing, so that the
+// replay to the second one can exceed it. Reaching this needs inputs beyond the default maximum
+// length of libFuzzer, e.g. `cargo fuzz run or -- -max_len=20000`.
+fuzz_target!(|data: &[u8]| {
+    let max = 2 * MAX_IDLE_PROGRESS;
+    async_codec_util_fuzz::fuzz(data, || {
+        decoder::or(decoder::until_delimiter(b";", max),
+                    decoder::until_delimiter(b"\n", 2 * max),
+                    2 * max)
+    });
+});
@@ -0,0 +1,34 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate async_codec_util;
+extern crate async_codec_util_fuzz;
+
+use async_codec_util::CodecError;
+use async_codec_util::decoder::{self, AndThen, BoxDecoder, BoxedDecode, Recursion};
+use async_codec_util::field;
+
+// Decodes the given number of trees, yielding their total number of nodes.
+fn children(rec: Recursion<'static, usize, CodecError>,
+            count: u8)
+            -> BoxDecoder<'static, usize, CodecError> {
+    if count == 0 {
+        return decoder::map(decoder::err_into(decoder::skip(0)), |_| 0).boxed();
+    }
+
+    AndThen::new(rec.decoder(), move |nodes| {
+        decoder::map(children(rec, count - 1), move |rest| nodes + rest)
+    })
+            .boxed()
+}
+
+// A tree is the number of its children, followed by the children.
+fuzz_target!(|data: &[u8]| {
+    async_codec_util_fuzz::fuzz(data, || {
+        decoder::recursive(32, |rec| {
+            AndThen::new(decoder::err_into::<CodecError, _>(field::decoder::<u8>()),
+                         move |count| decoder::map(children(rec, count), |nodes| nodes + 1))
+                    .boxed()
+        })
+    });
+});
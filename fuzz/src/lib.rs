@@ -0,0 +1,61 @@
+//! Shared setup of the fuzz targets: an allocator enforcing a memory limit per run, and a
+//! wrapper around `testing::fuzz_decoder` using it.
+
+extern crate async_codec;
+extern crate async_codec_util;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use async_codec::AsyncDecode;
+use async_codec_util::testing::fuzz_decoder;
+
+/// The number of bytes a decoder may allocate regardless of the input length.
+pub const BASE_ALLOCATION: usize = 1 << 20;
+
+/// The number of bytes a decoder may allocate per byte of input, in addition to the
+/// `BASE_ALLOCATION`.
+pub const ALLOCATION_PER_BYTE: usize = 64;
+
+// Fails all allocations that would exceed the current limit.
+struct LimitedAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+unsafe impl GlobalAlloc for LimitedAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let before = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst);
+        if before + layout.size() > LIMIT.load(Ordering::SeqCst) {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+            return ptr::null_mut();
+        }
+
+        let ptr = System.alloc(layout);
+        if ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: LimitedAlloc = LimitedAlloc;
+
+/// Run `testing::fuzz_decoder`, aborting if the decoder allocates more than `BASE_ALLOCATION`
+/// plus `ALLOCATION_PER_BYTE` times the input length.
+pub fn fuzz<D, F>(data: &[u8], make_dec: F)
+    where F: FnOnce() -> D,
+          D: AsyncDecode
+{
+    let allowed = BASE_ALLOCATION + ALLOCATION_PER_BYTE * data.len();
+    LIMIT.store(ALLOCATED.load(Ordering::SeqCst) + allowed, Ordering::SeqCst);
+    fuzz_decoder(data, make_dec);
+    LIMIT.store(usize::MAX, Ordering::SeqCst);
+}
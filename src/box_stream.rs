@@ -0,0 +1,554 @@
+//! Encrypted and authenticated framing via the
+//! [box-stream](https://ssbc.github.io/scuttlebutt-protocol-guide/#box-stream) protocol used
+//! after a secret-handshake.
+//!
+//! Data is split into chunks of at most 4096 bytes. Each chunk is sent as a 34 byte header,
+//! sealed with XSalsa20-Poly1305 and containing the chunk length and the MAC of the body,
+//! followed by the encrypted body. The stream is terminated by a header of sealed zeros.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, AsyncEncode, AsyncEncodeLen, DecodeError, PollDec, PollEnc};
+use crypto_secretbox::{AeadInPlace, Key, KeyInit, Nonce, Tag, XSalsa20Poly1305};
+use futures_core::{Async, Poll};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, AsyncWrite, Error as FutIoErr, ErrorKind};
+
+use decoder::ChunkReader;
+
+/// The maximum number of plaintext bytes in a single box-stream frame.
+pub const MAX_BODY: usize = 4096;
+
+const HEADER: usize = 34;
+const TAG: usize = 16;
+
+/// Create a `BoxEncoder`, encrypting the output of `enc` with the given key and starting nonce.
+pub fn encode<C>(enc: C, key: &[u8; 32], nonce: &[u8; 24]) -> BoxEncoder<C> {
+    BoxEncoder::new(enc, key, nonce)
+}
+
+/// Create a `BoxDecoder`, decrypting data with the given key and starting nonce and feeding it to
+/// `dec`.
+pub fn decode<D>(dec: D, key: &[u8; 32], nonce: &[u8; 24]) -> BoxDecoder<D>
+    where D: AsyncDecode
+{
+    BoxDecoder::new(dec, key, nonce)
+}
+
+fn increment(nonce: &mut [u8; 24]) {
+    for byte in nonce.iter_mut().rev() {
+        *byte = byte.wrapping_add(1);
+        if *byte != 0 {
+            break;
+        }
+    }
+}
+
+fn new_cipher(key: &[u8; 32]) -> XSalsa20Poly1305 {
+    XSalsa20Poly1305::new(Key::from_slice(key))
+}
+
+// Encrypt `buf` in place, returning the MAC.
+fn seal(cipher: &XSalsa20Poly1305, nonce: &[u8; 24], buf: &mut [u8]) -> Tag {
+    cipher
+        .encrypt_in_place_detached(Nonce::from_slice(nonce), b"", buf)
+        .expect("box-stream frames are far below the maximum message size")
+}
+
+// Decrypt `buf` in place, returning whether it was authentic.
+fn open(cipher: &XSalsa20Poly1305, nonce: &[u8; 24], buf: &mut [u8], tag: &[u8]) -> bool {
+    cipher
+        .decrypt_in_place_detached(Nonce::from_slice(nonce), b"", buf, Tag::from_slice(tag))
+        .is_ok()
+}
+
+/// The error of a `BoxDecoder`.
+#[derive(Debug)]
+pub enum BoxStreamError<E> {
+    /// A header or body failed authentication.
+    Unauthenticated,
+    /// A header announced a body of the contained, invalid length.
+    InvalidLength(usize),
+    /// The inner decoder finished before all data of the stream was consumed.
+    TrailingData,
+    /// The inner decoder errored.
+    Inner(E),
+}
+
+impl<E: Display> Display for BoxStreamError<E> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            BoxStreamError::Unauthenticated => write!(f, "Box-stream frame failed authentication"),
+            BoxStreamError::InvalidLength(len) => {
+                write!(f, "Invalid box-stream body length {}", len)
+            }
+            BoxStreamError::TrailingData => write!(f, "Trailing data in box-stream"),
+            BoxStreamError::Inner(ref err) => write!(f, "Box-stream inner error: {}", err),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for BoxStreamError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            BoxStreamError::Inner(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// Collects the output of the inner encoder in memory, up to `MAX_BODY` bytes.
+struct ChunkWriter<'a>(&'a mut Vec<u8>);
+
+impl<'a> AsyncWrite for ChunkWriter<'a> {
+    fn poll_write(&mut self, _: &mut Context, buf: &[u8]) -> Poll<usize, FutIoErr> {
+        let len = buf.len().min(MAX_BODY - self.0.len());
+        self.0.extend_from_slice(&buf[..len]);
+        Ok(Async::Ready(len))
+    }
+
+    fn poll_flush(&mut self, _: &mut Context) -> Poll<(), FutIoErr> {
+        Ok(Async::Ready(()))
+    }
+
+    fn poll_close(&mut self, _: &mut Context) -> Poll<(), FutIoErr> {
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Wraps an encoder, encrypting its output into box-stream frames and terminating the stream with
+/// a goodbye frame.
+pub struct BoxEncoder<C> {
+    // `None` once the inner encoder is done.
+    enc: Option<C>,
+    cipher: XSalsa20Poly1305,
+    nonce: [u8; 24],
+    // Output of the inner encoder that has not been sealed yet.
+    plain: Vec<u8>,
+    // The sealed frame currently being written, and how much of it has been written.
+    frame: Vec<u8>,
+    offset: usize,
+    goodbye: bool,
+}
+
+impl<C> BoxEncoder<C> {
+    /// Create a new `BoxEncoder`, encrypting the output of `enc` with the given key and starting
+    /// nonce.
+    pub fn new(enc: C, key: &[u8; 32], nonce: &[u8; 24]) -> BoxEncoder<C> {
+        BoxEncoder {
+            enc: Some(enc),
+            cipher: new_cipher(key),
+            nonce: *nonce,
+            plain: Vec::with_capacity(MAX_BODY),
+            frame: Vec::with_capacity(HEADER + MAX_BODY),
+            offset: 0,
+            goodbye: false,
+        }
+    }
+
+    fn seal_frame(&mut self) {
+        self.frame.clear();
+        self.offset = 0;
+
+        let mut header = [0; HEADER];
+        if self.plain.is_empty() {
+            self.goodbye = true;
+        } else {
+            let mut body_nonce = self.nonce;
+            increment(&mut body_nonce);
+            let body_tag = seal(&self.cipher, &body_nonce, &mut self.plain);
+
+            header[TAG] = (self.plain.len() >> 8) as u8;
+            header[TAG + 1] = self.plain.len() as u8;
+            header[TAG + 2..].copy_from_slice(&body_tag);
+        }
+
+        let header_tag = seal(&self.cipher, &self.nonce, &mut header[TAG..]);
+        header[..TAG].copy_from_slice(&header_tag);
+        increment(&mut self.nonce);
+        increment(&mut self.nonce);
+
+        self.frame.extend_from_slice(&header);
+        self.frame.extend_from_slice(&self.plain);
+        self.plain.clear();
+    }
+}
+
+impl<C: AsyncEncode> AsyncEncode for BoxEncoder<C> {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        while self.offset == self.frame.len() {
+            if self.goodbye {
+                return PollEnc::Done(0);
+            }
+
+            while self.plain.len() < MAX_BODY {
+                match self.enc.take() {
+                    None => break,
+                    Some(enc) => {
+                        match enc.poll_encode(cx, &mut ChunkWriter(&mut self.plain)) {
+                            PollEnc::Done(_) => {}
+                            PollEnc::Progress(enc, _) => self.enc = Some(enc),
+                            PollEnc::Pending(enc) => {
+                                self.enc = Some(enc);
+                                return PollEnc::Pending(self);
+                            }
+                            PollEnc::Errored(err) => return PollEnc::Errored(err),
+                        }
+                    }
+                }
+            }
+
+            self.seal_frame();
+        }
+
+        match writer.poll_write(cx, &self.frame[self.offset..]) {
+            Ok(Async::Ready(0)) => {
+                PollEnc::Errored(FutIoErr::new(ErrorKind::WriteZero, "box-stream"))
+            }
+            Ok(Async::Ready(written)) => {
+                self.offset += written;
+                if self.goodbye && self.offset == self.frame.len() {
+                    PollEnc::Done(written)
+                } else {
+                    PollEnc::Progress(self, written)
+                }
+            }
+            Ok(Async::Pending) => PollEnc::Pending(self),
+            Err(err) => PollEnc::Errored(err),
+        }
+    }
+}
+
+impl<C: AsyncEncodeLen> AsyncEncodeLen for BoxEncoder<C> {
+    // `usize::div_ceil` would need Rust 1.73.
+    #[allow(clippy::manual_div_ceil)]
+    fn remaining_bytes(&self) -> usize {
+        let pending = self.frame.len() - self.offset;
+        if self.goodbye {
+            return pending;
+        }
+
+        let plain = self.plain.len() + self.enc.as_ref().map_or(0, |enc| enc.remaining_bytes());
+        let frames = (plain + MAX_BODY - 1) / MAX_BODY;
+        pending + plain + frames * HEADER + HEADER
+    }
+}
+
+fn data_error<T, S, E>(err: BoxStreamError<E>) -> PollDec<T, S, BoxStreamError<E>> {
+    PollDec::Errored(DecodeError::DataError(err))
+}
+
+enum State<D, I> {
+    Decoding(D),
+    // The inner decoder is done, the rest of the stream must not contain any data.
+    Finishing(I),
+}
+
+/// Wraps a decoder, decrypting and authenticating box-stream frames and feeding their contents to
+/// the inner decoder, up to and including the goodbye frame.
+pub struct BoxDecoder<D>
+    where D: AsyncDecode
+{
+    state: State<D, D::Item>,
+    cipher: XSalsa20Poly1305,
+    nonce: [u8; 24],
+    header: [u8; HEADER],
+    header_read: usize,
+    // The MAC of the current body, `None` while reading a header.
+    body_tag: Option<[u8; TAG]>,
+    // Encrypted body being read, or decrypted data of which `body[offset..]` has not been
+    // decoded yet.
+    body: Vec<u8>,
+    body_read: usize,
+    offset: usize,
+    goodbye: bool,
+}
+
+impl<D> BoxDecoder<D>
+    where D: AsyncDecode
+{
+    /// Create a new `BoxDecoder`, decrypting data with the given key and starting nonce and
+    /// feeding it to `dec`.
+    pub fn new(dec: D, key: &[u8; 32], nonce: &[u8; 24]) -> BoxDecoder<D> {
+        BoxDecoder {
+            state: State::Decoding(dec),
+            cipher: new_cipher(key),
+            nonce: *nonce,
+            header: [0; HEADER],
+            header_read: 0,
+            body_tag: None,
+            body: Vec::with_capacity(MAX_BODY),
+            body_read: 0,
+            offset: 0,
+            goodbye: false,
+        }
+    }
+
+    // Authenticate and process a fully read header.
+    fn open_header(&mut self) -> Result<(), BoxStreamError<D::Error>> {
+        let (header_tag, header) = self.header.split_at_mut(TAG);
+        if !open(&self.cipher, &self.nonce, header, header_tag) {
+            return Err(BoxStreamError::Unauthenticated);
+        }
+        self.header_read = 0;
+
+        if header.iter().all(|byte| *byte == 0) {
+            self.goodbye = true;
+            return Ok(());
+        }
+
+        let len = ((header[0] as usize) << 8) | header[1] as usize;
+        if len == 0 || len > MAX_BODY {
+            return Err(BoxStreamError::InvalidLength(len));
+        }
+
+        let mut body_tag = [0; TAG];
+        body_tag.copy_from_slice(&header[2..]);
+        self.body_tag = Some(body_tag);
+        self.body.clear();
+        self.body.resize(len, 0);
+        self.body_read = 0;
+        Ok(())
+    }
+
+    // Authenticate and decrypt a fully read body.
+    fn open_body(&mut self, body_tag: [u8; TAG]) -> Result<(), BoxStreamError<D::Error>> {
+        let mut body_nonce = self.nonce;
+        increment(&mut body_nonce);
+        if !open(&self.cipher, &body_nonce, &mut self.body, &body_tag) {
+            return Err(BoxStreamError::Unauthenticated);
+        }
+
+        increment(&mut self.nonce);
+        increment(&mut self.nonce);
+        self.offset = 0;
+        Ok(())
+    }
+}
+
+impl<D> AsyncDecode for BoxDecoder<D>
+    where D: AsyncDecode
+{
+    type Item = D::Item;
+    type Error = BoxStreamError<D::Error>;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        // The number of bytes read from `reader`, it is only polled once.
+        let mut read = None;
+
+        loop {
+            let decrypted = self.body_tag.is_none() && self.offset < self.body.len();
+
+            if decrypted || self.goodbye {
+                match self.state {
+                    State::Decoding(dec) => {
+                        let plain: &[u8] = if decrypted {
+                            &self.body[self.offset..]
+                        } else {
+                            &[]
+                        };
+                        let mut chunk = ChunkReader::new(plain, self.goodbye);
+                        let res = dec.poll_decode(cx, &mut chunk);
+                        self.offset = self.body.len() - chunk.remaining();
+
+                        match res {
+                            PollDec::Done(item, _) => self.state = State::Finishing(item),
+                            PollDec::Progress(dec, _) => self.state = State::Decoding(dec),
+                            // The inner decoder waits for the next frame.
+                            PollDec::Pending(dec) if chunk.starved() => {
+                                self.state = State::Decoding(dec)
+                            }
+                            PollDec::Pending(dec) => {
+                                self.state = State::Decoding(dec);
+                                return match read {
+                                           Some(read) => PollDec::Progress(self, read),
+                                           None => PollDec::Pending(self),
+                                       };
+                            }
+                            PollDec::Errored(DecodeError::DataError(err)) => {
+                                return data_error(BoxStreamError::Inner(err))
+                            }
+                            PollDec::Errored(DecodeError::ReaderError(err)) => {
+                                return PollDec::Errored(DecodeError::ReaderError(err))
+                            }
+                        }
+                        continue;
+                    }
+
+                    State::Finishing(item) => {
+                        if decrypted {
+                            return data_error(BoxStreamError::TrailingData);
+                        } else if self.goodbye {
+                            return PollDec::Done(item, read.unwrap_or(0));
+                        } else {
+                            self.state = State::Finishing(item);
+                        }
+                    }
+                }
+            }
+
+            // There is no decrypted data, so read (more of) the next frame.
+            if let Some(read) = read {
+                return PollDec::Progress(self, read);
+            }
+
+            let res = match self.body_tag {
+                None => reader.poll_read(cx, &mut self.header[self.header_read..]),
+                Some(_) => reader.poll_read(cx, &mut self.body[self.body_read..]),
+            };
+
+            match res {
+                Ok(Async::Ready(0)) => {
+                    return PollDec::Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "box-stream")
+                                                .into())
+                }
+                Ok(Async::Ready(n)) => {
+                    read = Some(n);
+                    let opened = match self.body_tag {
+                        None => {
+                            self.header_read += n;
+                            if self.header_read == HEADER {
+                                self.open_header()
+                            } else {
+                                Ok(())
+                            }
+                        }
+                        Some(body_tag) => {
+                            self.body_read += n;
+                            if self.body_read == self.body.len() {
+                                self.body_tag = None;
+                                self.open_body(body_tag)
+                            } else {
+                                Ok(())
+                            }
+                        }
+                    };
+
+                    if let Err(err) = opened {
+                        return data_error(err);
+                    }
+                }
+                Ok(Async::Pending) => return PollDec::Pending(self),
+                Err(err) => return PollDec::Errored(err.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use async_codec::DecodeError;
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use async_byteorder::{decode_i32_native, decode_u64_native, encode_i32_native,
+                          encode_u64_native};
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::{decode as run_decode, encode as run_encode};
+    use super::super::testing::{check_wakeups, test_codec_len, GreedyDecoder};
+    use super::super::decoder::{chain as dec_chain, skip, until_delimiter as dec_until_delimiter};
+    use super::super::encoder::{chain as enc_chain, until_delimiter as enc_until_delimiter};
+
+    const KEY: [u8; 32] = [7; 32];
+    const NONCE: [u8; 24] = [0xff; 24];
+
+    fn encrypt(data: &[u8]) -> Vec<u8> {
+        let enc = encode(enc_until_delimiter(data, b"\n").unwrap(), &KEY, &NONCE);
+        let expected_len = enc.remaining_bytes();
+        let (writer, written) = block_on(run_encode(Cursor::new(Vec::new()), enc))
+            .map_err(|(_, err)| err)
+            .unwrap();
+        assert_eq!(written, expected_len);
+        writer.into_inner()
+    }
+
+    #[test]
+    fn multiple_frames() {
+        let data = vec![42; 3 * MAX_BODY];
+        let encrypted = encrypt(&data);
+        assert_eq!(encrypted.len(), data.len() + 1 + 5 * HEADER);
+
+        let dec = decode(dec_until_delimiter(b"\n", data.len()), &KEY, &NONCE);
+        let (rest, item, read) = block_on(run_decode(&encrypted[..], dec))
+            .map_err(|(_, err)| err)
+            .unwrap();
+        assert_eq!(item, data);
+        assert_eq!(read, encrypted.len());
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn tampering() {
+        let encrypted = encrypt(b"hello");
+
+        for i in 0..encrypted.len() {
+            let mut tampered = encrypted.clone();
+            tampered[i] ^= 1;
+            let dec = decode(dec_until_delimiter(b"\n", 16), &KEY, &NONCE);
+            match block_on(run_decode(&tampered[..], dec)) {
+                Err((_, DecodeError::DataError(BoxStreamError::Unauthenticated))) => {}
+                _ => panic!("tampering with byte {} was not detected", i),
+            }
+        }
+
+        // A stream truncated before the goodbye frame is not accepted.
+        let dec = decode(dec_until_delimiter(b"\n", 16), &KEY, &NONCE);
+        assert!(block_on(run_decode(&encrypted[..encrypted.len() - HEADER], dec)).is_err());
+    }
+
+    #[test]
+    fn repeated_reads() {
+        let data = vec![42; 2 * MAX_BODY + 100];
+        let encrypted = encrypt(&data);
+        let make_dec = || {
+            decode(dec_chain(GreedyDecoder::new(data.len()), skip(1)),
+                   &KEY,
+                   &NONCE)
+        };
+
+        let (_, item, read) = block_on(run_decode(&encrypted[..], make_dec()))
+            .map_err(|(_, err)| err)
+            .unwrap();
+        assert_eq!((item.0, read), (data.clone(), encrypted.len()));
+        check_wakeups(make_dec, &encrypted);
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, int_0: i32, int_1: u64) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let dec = decode(dec_chain(decode_i32_native(), decode_u64_native()), &KEY, &NONCE);
+            let enc = encode(enc_chain(encode_i32_native(int_0), encode_u64_native(int_1)), &KEY, &NONCE);
+            let test_outcome = test_codec_len(r, w, dec, enc);
+            test_outcome.1 && (test_outcome.0).0 == int_0 && (test_outcome.0).1 == int_1
+        }
+    }
+
+    quickcheck! {
+        fn spanning_frames(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, extra: u16) -> bool {
+            let data = vec![42; MAX_BODY + extra as usize];
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let dec = dec_chain(GreedyDecoder::new(data.len()), skip(1));
+            let dec = decode(dec, &KEY, &NONCE);
+            let enc = encode(enc_until_delimiter(data.clone(), b"\n").unwrap(), &KEY, &NONCE);
+            let test_outcome = test_codec_len(r, w, dec, enc);
+            test_outcome.1 && (test_outcome.0).0 == data
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Checksums for use with `decoder::Checksummed` and `encoder::Checksummed`.
+
+/// An incrementally computed checksum over some bytes.
+pub trait Checksum {
+    /// The number of bytes of the digest produced by `digest`.
+    const LEN: usize;
+
+    /// Feed some bytes into the checksum.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Return the checksum of all bytes fed into this so far, as `LEN` bytes.
+    fn digest(&self) -> Vec<u8>;
+}
+
+const fn crc32_table(poly: u32) -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ poly } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE: [u32; 256] = crc32_table(0xedb8_8320);
+static CRC32C_TABLE: [u32; 256] = crc32_table(0x82f6_3b78);
+
+fn crc32_update(table: &[u32; 256], mut crc: u32, bytes: &[u8]) -> u32 {
+    for byte in bytes {
+        crc = table[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+fn be_bytes(n: u32) -> Vec<u8> {
+    vec![(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8, n as u8]
+}
+
+/// The CRC-32 (IEEE 802.3) checksum, with a big-endian digest.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    /// Create a new `Crc32` over zero bytes.
+    pub fn new() -> Crc32 {
+        Crc32(0xffff_ffff)
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+impl Checksum for Crc32 {
+    const LEN: usize = 4;
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.0 = crc32_update(&CRC32_TABLE, self.0, bytes);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        be_bytes(!self.0)
+    }
+}
+
+/// The CRC-32C (Castagnoli) checksum, with a big-endian digest.
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32c(u32);
+
+impl Crc32c {
+    /// Create a new `Crc32c` over zero bytes.
+    pub fn new() -> Crc32c {
+        Crc32c(0xffff_ffff)
+    }
+}
+
+impl Default for Crc32c {
+    fn default() -> Crc32c {
+        Crc32c::new()
+    }
+}
+
+impl Checksum for Crc32c {
+    const LEN: usize = 4;
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.0 = crc32_update(&CRC32C_TABLE, self.0, bytes);
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        be_bytes(!self.0)
+    }
+}
+
+const ADLER_MOD: u32 = 65521;
+
+/// The Adler-32 checksum, with a big-endian digest.
+#[derive(Debug, Clone, Copy)]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Adler32 {
+    /// Create a new `Adler32` over zero bytes.
+    pub fn new() -> Adler32 {
+        Adler32 { a: 1, b: 0 }
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Adler32 {
+        Adler32::new()
+    }
+}
+
+impl Checksum for Adler32 {
+    const LEN: usize = 4;
+
+    fn update(&mut self, bytes: &[u8]) {
+        // 5552 is the largest number of bytes that can be summed without overflowing a u32.
+        for chunk in bytes.chunks(5552) {
+            for byte in chunk {
+                self.a += *byte as u32;
+                self.b += self.a;
+            }
+            self.a %= ADLER_MOD;
+            self.b %= ADLER_MOD;
+        }
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        be_bytes((self.b << 16) | self.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest<C: Checksum>(mut checksum: C, data: &[u8]) -> Vec<u8> {
+        let (left, right) = data.split_at(data.len() / 2);
+        checksum.update(left);
+        checksum.update(right);
+        checksum.digest()
+    }
+
+    #[test]
+    fn check_values() {
+        assert_eq!(digest(Crc32::new(), b"123456789"), vec![0xcb, 0xf4, 0x39, 0x26]);
+        assert_eq!(digest(Crc32c::new(), b"123456789"), vec![0xe3, 0x06, 0x92, 0x83]);
+        assert_eq!(digest(Adler32::new(), b"Wikipedia"), vec![0x11, 0xe6, 0x03, 0x98]);
+        assert_eq!(digest(Crc32::new(), b""), vec![0, 0, 0, 0]);
+        assert_eq!(digest(Adler32::new(), &[0xff; 100_000]),
+                   be_bytes({
+                                let mut a = 1u64;
+                                let mut b = 0u64;
+                                for _ in 0..100_000 {
+                                    a = (a + 0xff) % 65521;
+                                    b = (b + a) % 65521;
+                                }
+                                ((b << 16) | a) as u32
+                            }));
+    }
+}
@@ -0,0 +1,183 @@
+//! Compression algorithms for use with `encoder::Compressed` and `decoder::Decompressed`.
+//!
+//! Implementations for deflate and zstd are available via the `flate2` and `zstd` features.
+
+use futures_io::Error as FutIoErr;
+
+/// The outcome of a single call to `Compressor::compress` or `Decompressor::decompress`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// How many bytes of the input were consumed.
+    pub consumed: usize,
+    /// How many bytes were written to the output.
+    pub produced: usize,
+    /// Whether the compressed stream has been completed.
+    pub done: bool,
+}
+
+/// A streaming compression algorithm.
+pub trait Compressor {
+    /// Compress a prefix of `input` into a prefix of `output`.
+    ///
+    /// `finish` indicates that `input` contains all remaining uncompressed data, in which case the
+    /// compressor should complete the stream once all of it has been consumed.
+    fn compress(&mut self,
+                input: &[u8],
+                output: &mut [u8],
+                finish: bool)
+                -> Result<Step, FutIoErr>;
+}
+
+/// A streaming decompression algorithm.
+pub trait Decompressor {
+    /// Decompress a prefix of `input` into a prefix of `output`.
+    ///
+    /// This may also be called with an empty input, to retrieve data that did not fit into the
+    /// output of a previous call.
+    fn decompress(&mut self, input: &[u8], output: &mut [u8]) -> Result<Step, FutIoErr>;
+}
+
+#[cfg(feature = "flate2")]
+pub use self::deflate::{DeflateCompressor, DeflateDecompressor};
+
+#[cfg(feature = "flate2")]
+mod deflate {
+    use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+    use futures_io::{Error as FutIoErr, ErrorKind};
+
+    use super::{Compressor, Decompressor, Step};
+
+    /// Compresses via deflate, optionally with a zlib header.
+    pub struct DeflateCompressor(Compress);
+
+    impl DeflateCompressor {
+        /// Create a new `DeflateCompressor` with the given compression level (0 to 9), emitting a
+        /// zlib header and trailer if `zlib` is true.
+        pub fn new(level: u32, zlib: bool) -> DeflateCompressor {
+            DeflateCompressor(Compress::new(Compression::new(level), zlib))
+        }
+    }
+
+    impl Compressor for DeflateCompressor {
+        // `io::Error::other` would need Rust 1.74.
+        #[allow(clippy::io_other_error)]
+        fn compress(&mut self,
+                    input: &[u8],
+                    output: &mut [u8],
+                    finish: bool)
+                    -> Result<Step, FutIoErr> {
+            let (total_in, total_out) = (self.0.total_in(), self.0.total_out());
+            let flush = if finish {
+                FlushCompress::Finish
+            } else {
+                FlushCompress::None
+            };
+
+            let status = self.0
+                .compress(input, output, flush)
+                .map_err(|err| FutIoErr::new(ErrorKind::Other, err))?;
+
+            Ok(Step {
+                   consumed: (self.0.total_in() - total_in) as usize,
+                   produced: (self.0.total_out() - total_out) as usize,
+                   done: status == Status::StreamEnd,
+               })
+        }
+    }
+
+    /// Decompresses deflate data, optionally with a zlib header.
+    pub struct DeflateDecompressor(Decompress);
+
+    impl DeflateDecompressor {
+        /// Create a new `DeflateDecompressor`, expecting a zlib header and trailer if `zlib` is
+        /// true.
+        pub fn new(zlib: bool) -> DeflateDecompressor {
+            DeflateDecompressor(Decompress::new(zlib))
+        }
+    }
+
+    impl Decompressor for DeflateDecompressor {
+        fn decompress(&mut self, input: &[u8], output: &mut [u8]) -> Result<Step, FutIoErr> {
+            let (total_in, total_out) = (self.0.total_in(), self.0.total_out());
+
+            let status = self.0
+                .decompress(input, output, FlushDecompress::None)
+                .map_err(|err| FutIoErr::new(ErrorKind::InvalidData, err))?;
+
+            Ok(Step {
+                   consumed: (self.0.total_in() - total_in) as usize,
+                   produced: (self.0.total_out() - total_out) as usize,
+                   done: status == Status::StreamEnd,
+               })
+        }
+    }
+}
+
+#[cfg(feature = "zstd")]
+pub use self::zstandard::{ZstdCompressor, ZstdDecompressor};
+
+#[cfg(feature = "zstd")]
+mod zstandard {
+    use futures_io::Error as FutIoErr;
+    use zstd::stream::raw::{Decoder, Encoder, Operation, OutBuffer};
+
+    use super::{Compressor, Decompressor, Step};
+
+    /// Compresses a single zstd frame.
+    pub struct ZstdCompressor(Encoder<'static>);
+
+    impl ZstdCompressor {
+        /// Create a new `ZstdCompressor` with the given compression level (0 uses zstd's default).
+        pub fn new(level: i32) -> Result<ZstdCompressor, FutIoErr> {
+            Ok(ZstdCompressor(Encoder::new(level)?))
+        }
+    }
+
+    impl Compressor for ZstdCompressor {
+        fn compress(&mut self,
+                    input: &[u8],
+                    output: &mut [u8],
+                    finish: bool)
+                    -> Result<Step, FutIoErr> {
+            if !input.is_empty() || !finish {
+                let status = self.0.run_on_buffers(input, output)?;
+                Ok(Step {
+                       consumed: status.bytes_read,
+                       produced: status.bytes_written,
+                       done: false,
+                   })
+            } else {
+                let mut output = OutBuffer::around(output);
+                let remaining = self.0.finish(&mut output, true)?;
+                Ok(Step {
+                       consumed: 0,
+                       produced: output.pos(),
+                       done: remaining == 0,
+                   })
+            }
+        }
+    }
+
+    /// Decompresses a single zstd frame.
+    pub struct ZstdDecompressor(Decoder<'static>);
+
+    impl ZstdDecompressor {
+        /// Create a new `ZstdDecompressor`.
+        pub fn new() -> Result<ZstdDecompressor, FutIoErr> {
+            Ok(ZstdDecompressor(Decoder::new()?))
+        }
+    }
+
+    impl Decompressor for ZstdDecompressor {
+        fn decompress(&mut self, input: &[u8], output: &mut [u8]) -> Result<Step, FutIoErr> {
+            let status = self.0.run_on_buffers(input, output)?;
+            Ok(Step {
+                   consumed: status.bytes_read,
+                   produced: status.bytes_written,
+                   // zstd signals the end of a frame by hinting that no further input is needed,
+                   // which only happens once all decompressed data has been flushed.
+                   done: status.remaining == 0,
+               })
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::Async::{Ready, Pending as AsyncPending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+
+use sink::Position;
+
+/// The error of an `Aligned`.
+#[derive(Debug)]
+pub enum AlignedError<E> {
+    /// A padding byte at the contained offset was not zero.
+    NonZeroPadding(usize),
+    /// The inner decoder errored.
+    Inner(E),
+}
+
+impl<E: Display> Display for AlignedError<E> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            AlignedError::NonZeroPadding(offset) => {
+                write!(f, "Non-zero padding byte at offset {}", offset)
+            }
+            AlignedError::Inner(ref err) => write!(f, "Aligned inner error: {}", err),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for AlignedError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            AlignedError::NonZeroPadding(_) => None,
+            AlignedError::Inner(ref err) => Some(err),
+        }
+    }
+}
+
+// The number of bytes needed to advance `offset` to a multiple of `alignment`.
+fn padding(alignment: usize, offset: usize) -> usize {
+    (alignment - offset % alignment) % alignment
+}
+
+enum State<D: AsyncDecode> {
+    Inner(D),
+    // The decoded item and the number of padding bytes still to consume.
+    Padding(D::Item, usize),
+}
+
+/// Wraps a decoder, consuming padding after it until the offset is a multiple of some alignment.
+///
+/// The offset is relative to the start of the `Aligned`, unless it tracks a `sink::Position` via
+/// `tracking`. A tracking `Aligned` pads to the absolute offset in the stream, wherever it is
+/// placed in a composition of decoders.
+pub struct Aligned<D: AsyncDecode> {
+    state: State<D>,
+    alignment: usize,
+    offset: usize,
+    position: Option<Position>,
+    verify: bool,
+}
+
+impl<D: AsyncDecode> Aligned<D> {
+    /// Create a new `Aligned`, decoding via `dec` and then skipping padding up to the next
+    /// multiple of `alignment`.
+    ///
+    /// Panics if `alignment` is zero.
+    pub fn new(dec: D, alignment: usize) -> Aligned<D> {
+        assert!(alignment > 0, "Alignment must not be zero");
+
+        Aligned {
+            state: State::Inner(dec),
+            alignment,
+            offset: 0,
+            position: None,
+            verify: false,
+        }
+    }
+
+    /// Pad to the offset read from the given `Position` instead of the relative one.
+    pub fn tracking(mut self, position: Position) -> Aligned<D> {
+        self.position = Some(position);
+        self
+    }
+
+    /// Error with `NonZeroPadding` if a padding byte is not zero, instead of ignoring its value.
+    pub fn verify_padding(mut self) -> Aligned<D> {
+        self.verify = true;
+        self
+    }
+}
+
+// The offset of the next byte to read, given the number of bytes read by the `Aligned` so far.
+fn current_offset(position: &Option<Position>, offset: usize) -> usize {
+    match *position {
+        Some(ref position) => position.get(),
+        None => offset,
+    }
+}
+
+impl<D: AsyncDecode> AsyncDecode for Aligned<D> {
+    type Item = D::Item;
+    type Error = AlignedError<D::Error>;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        match self.state {
+            State::Inner(dec) => {
+                match dec.poll_decode(cx, reader) {
+                    Done(item, read) => {
+                        self.offset += read;
+                        let offset = current_offset(&self.position, self.offset);
+                        let padding = padding(self.alignment, offset);
+                        if padding == 0 {
+                            Done(item, read)
+                        } else {
+                            self.state = State::Padding(item, padding);
+                            Progress(self, read)
+                        }
+                    }
+                    Progress(dec, read) => {
+                        self.offset += read;
+                        self.state = State::Inner(dec);
+                        Progress(self, read)
+                    }
+                    Pending(dec) => {
+                        self.state = State::Inner(dec);
+                        Pending(self)
+                    }
+                    Errored(DecodeError::ReaderError(err)) => {
+                        Errored(DecodeError::ReaderError(err))
+                    }
+                    Errored(DecodeError::DataError(err)) => {
+                        Errored(DecodeError::DataError(AlignedError::Inner(err)))
+                    }
+                }
+            }
+
+            State::Padding(item, remaining) => {
+                let mut buf = [0u8; 64];
+                let len = buf.len().min(remaining);
+                let offset = current_offset(&self.position, self.offset);
+
+                match reader.poll_read(cx, &mut buf[..len]) {
+                    Ok(Ready(0)) => {
+                        Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "aligned").into())
+                    }
+                    Ok(Ready(read)) => {
+                        if self.verify {
+                            if let Some(i) = buf[..read].iter().position(|&byte| byte != 0) {
+                                let err = AlignedError::NonZeroPadding(offset + i);
+                                return Errored(DecodeError::DataError(err));
+                            }
+                        }
+
+                        self.offset += read;
+                        if read == remaining {
+                            Done(item, read)
+                        } else {
+                            self.state = State::Padding(item, remaining - read);
+                            Progress(self, read)
+                        }
+                    }
+                    Ok(AsyncPending) => {
+                        self.state = State::Padding(item, remaining);
+                        Pending(self)
+                    }
+                    Err(err) => Errored(err.into()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use async_codec::{AsyncEncodeLen, DecodeError};
+    use async_byteorder::{decode_u8, decode_u16_be, encode_u8, encode_u16_be};
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::{decode, encode};
+    use super::super::super::decoder::{chain, map, tee};
+    use super::super::super::testing::test_codec_len;
+    use super::super::super::encoder::{self, Aligned as EncAligned};
+
+    #[test]
+    fn skips_padding() {
+        let data: &[u8] = &[7, 0, 0, 0, 42];
+        let (rest, item, read) = block_on(decode(data, Aligned::new(decode_u8(), 4))).unwrap();
+        assert_eq!((rest, item, read), (&[42][..], 7, 4));
+
+        let position = Position::new(2);
+        let dec = tee(Aligned::new(decode_u8(), 4).tracking(position.clone()), position);
+        let (rest, _, read) = block_on(decode(data, dec)).unwrap();
+        assert_eq!((rest, read), (&[0, 0, 42][..], 2));
+
+        let data: &[u8] = &[7, 0, 1, 0];
+        match block_on(decode(data, Aligned::new(decode_u8(), 4).verify_padding())) {
+            Err((_, DecodeError::DataError(AlignedError::NonZeroPadding(2)))) => {}
+            _ => panic!("expected NonZeroPadding"),
+        }
+        assert!(block_on(decode(data, Aligned::new(decode_u8(), 4))).is_ok());
+    }
+
+    #[test]
+    fn encoder_from_position() {
+        let position = Position::new(2);
+        let tracked = position.clone();
+        let enc = encoder::then(encode_u8(1), move |_| {
+            EncAligned::new(encode_u16_be(2), 4).at_offset(tracked.get())
+        });
+        let (writer, written) = block_on(encode(Cursor::new(Vec::new()),
+                                                encoder::tee(enc, position)))
+                .unwrap();
+        assert_eq!((writer.into_inner(), written), (vec![1, 0, 2, 0, 0, 0], 6));
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, a: u8, b: u16, align: (u8, u8)) -> bool {
+            let (align_a, align_b) = (align.0 as usize + 1, align.1 as usize + 1);
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let position = Position::default();
+            let dec = chain(Aligned::new(decode_u8(), align_a)
+                                .tracking(position.clone())
+                                .verify_padding(),
+                            Aligned::new(decode_u16_be(), align_b)
+                                .tracking(position.clone())
+                                .verify_padding());
+            let dec = map(tee(dec, position), |(item, _)| item);
+
+            let first = EncAligned::new(encode_u8(a), align_a);
+            let first_len = first.remaining_bytes();
+            let enc = encoder::chain(first,
+                                     EncAligned::new(encode_u16_be(b), align_b)
+                                         .at_offset(first_len));
+            let test_outcome = test_codec_len(r, w, dec, enc);
+            test_outcome.1 && test_outcome.0 == (a, b)
+        }
+    }
+}
@@ -1,65 +1,96 @@
+use std::marker::PhantomData;
+
 use async_codec::{AsyncDecode, PollDec};
 use async_codec::PollDec::{Done, Progress, Pending, Errored};
 use futures_core::task::Context;
 use futures_io::AsyncRead;
 
+use super::convert_error;
+
 enum State<S, T, F> {
     First(S, F),
     Second(T),
 }
 
 /// Run a decoder and use the produced item to construct the next decoder to run.
-pub struct AndThen<S, T, F>(State<S, T, F>);
+///
+/// The errors of both decoders are converted into the error type `E` via `Into`. It defaults to
+/// the error of the first decoder, use `with_error` to choose another one.
+pub struct AndThen<S, T, F, E = <S as AsyncDecode>::Error>
+    where S: AsyncDecode
+{
+    state: State<S, T, F>,
+    _error: PhantomData<fn() -> E>,
+}
 
-impl<S, T, F> AndThen<S, T, F> {
+impl<S, T, F> AndThen<S, T, F>
+    where S: AsyncDecode
+{
     /// Run a decoder and use the produced item to construct the next decoder to run.
     pub fn new(first: S, f: F) -> AndThen<S, T, F> {
-        AndThen(State::First(first, f))
+        AndThen {
+            state: State::First(first, f),
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<S, T, F, E> AndThen<S, T, F, E>
+    where S: AsyncDecode
+{
+    /// Convert the errors of both decoders into `G` instead.
+    pub fn with_error<G>(self) -> AndThen<S, T, F, G> {
+        AndThen {
+            state: self.state,
+            _error: PhantomData,
+        }
     }
 }
 
-impl<S, T, F> AsyncDecode for AndThen<S, T, F>
+impl<S, T, F, E> AsyncDecode for AndThen<S, T, F, E>
     where S: AsyncDecode,
-          T: AsyncDecode<Error = S::Error>,
+          T: AsyncDecode,
+          S::Error: Into<E>,
+          T::Error: Into<E>,
           F: FnOnce(S::Item) -> T
 {
     type Item = T::Item;
-    type Error = T::Error;
+    type Error = E;
 
     fn poll_decode<R: AsyncRead>(mut self,
                                  cx: &mut Context,
                                  reader: &mut R)
                                  -> PollDec<Self::Item, Self, Self::Error> {
-        match self.0 {
+        match self.state {
             State::First(first, f) => {
                 match first.poll_decode(cx, reader) {
                     Done(item, read) => {
-                        self.0 = State::Second(f(item));
+                        self.state = State::Second(f(item));
                         Progress(self, read)
                     }
                     Progress(first, read) => {
-                        self.0 = State::First(first, f);
+                        self.state = State::First(first, f);
                         Progress(self, read)
                     }
                     Pending(first) => {
-                        self.0 = State::First(first, f);
+                        self.state = State::First(first, f);
                         Pending(self)
                     }
-                    Errored(err) => Errored(err),
+                    Errored(err) => Errored(convert_error(err)),
                 }
             }
             State::Second(second) => {
                 match second.poll_decode(cx, reader) {
                     Done(item, read) => Done(item, read),
                     Progress(second, read) => {
-                        self.0 = State::Second(second);
+                        self.state = State::Second(second);
                         Progress(self, read)
                     }
                     Pending(second) => {
-                        self.0 = State::Second(second);
+                        self.state = State::Second(second);
                         Pending(self)
                     }
-                    Errored(err) => Errored(err),
+                    Errored(err) => Errored(convert_error(err)),
                 }
             }
         }
@@ -0,0 +1,142 @@
+use async_codec::{AsyncDecode, PollDec};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncRead;
+
+// Object-safe version of `AsyncDecode`, polling in place rather than by value.
+trait DynDecode<Item, Err> {
+    fn poll_decode_dyn(&mut self,
+                       cx: &mut Context,
+                       reader: &mut dyn AsyncRead)
+                       -> PollDec<Item, (), Err>;
+}
+
+// Holds a decoder between polls, `None` once it is done.
+struct Slot<D>(Option<D>);
+
+impl<D: AsyncDecode> DynDecode<D::Item, D::Error> for Slot<D> {
+    fn poll_decode_dyn(&mut self,
+                       cx: &mut Context,
+                       mut reader: &mut dyn AsyncRead)
+                       -> PollDec<D::Item, (), D::Error> {
+        let dec = self.0.take().expect("Polled BoxDecoder after completion");
+        match dec.poll_decode(cx, &mut reader) {
+            Done(item, read) => Done(item, read),
+            Progress(dec, read) => {
+                self.0 = Some(dec);
+                Progress((), read)
+            }
+            Pending(dec) => {
+                self.0 = Some(dec);
+                Pending(())
+            }
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+/// A type-erased decoder, allowing decoders of different types to be used interchangeably.
+///
+/// This makes it possible to return different decoders from the branches of an `AndThen`, and to
+/// define recursive decoders.
+pub struct BoxDecoder<'a, Item, Err>(Box<dyn DynDecode<Item, Err> + 'a>);
+
+impl<'a, Item, Err> BoxDecoder<'a, Item, Err> {
+    /// Create a new `BoxDecoder`, erasing the type of the given decoder.
+    pub fn new<D>(dec: D) -> BoxDecoder<'a, Item, Err>
+        where D: AsyncDecode<Item = Item, Error = Err> + 'a
+    {
+        BoxDecoder(Box::new(Slot(Some(dec))))
+    }
+}
+
+impl<'a, Item, Err> AsyncDecode for BoxDecoder<'a, Item, Err> {
+    type Item = Item;
+    type Error = Err;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        match self.0.poll_decode_dyn(cx, reader) {
+            Done(item, read) => Done(item, read),
+            Progress((), read) => Progress(self, read),
+            Pending(()) => Pending(self),
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+/// Adds `boxed` to all decoders.
+pub trait BoxedDecode: AsyncDecode + Sized {
+    /// Erase the type of this decoder by turning it into a `BoxDecoder`.
+    fn boxed<'a>(self) -> BoxDecoder<'a, Self::Item, Self::Error>
+        where Self: 'a
+    {
+        BoxDecoder::new(self)
+    }
+}
+
+impl<D: AsyncDecode> BoxedDecode for D {}
+
+#[cfg(test)]
+mod tests {
+    use async_codec::{AsyncDecode, PollDec};
+    use async_byteorder::{decode_u8, decode_u16_be};
+    use futures_core::Never;
+    use futures_core::task::Context;
+    use futures_executor::block_on;
+    use futures_io::AsyncRead;
+
+    use super::super::super::decode;
+    use super::super::{map, AndThen, BoxDecoder, BoxedDecode};
+
+    // Yields an item without reading anything.
+    struct Ready<T>(T);
+
+    impl<T> AsyncDecode for Ready<T> {
+        type Item = T;
+        type Error = Never;
+
+        fn poll_decode<R: AsyncRead>(self,
+                                     _: &mut Context,
+                                     _: &mut R)
+                                     -> PollDec<Self::Item, Self, Self::Error> {
+            PollDec::Done(self.0, 0)
+        }
+    }
+
+    // A number is either a tagged u8 or a tagged big-endian u16.
+    fn number() -> BoxDecoder<'static, u16, Never> {
+        AndThen::new(decode_u8(), |tag| if tag == 0 {
+            map(decode_u8(), u16::from).boxed()
+        } else {
+            decode_u16_be().boxed()
+        })
+                .boxed()
+    }
+
+    // A list of numbers, each preceded by a 1 and terminated by a 0.
+    fn list() -> BoxDecoder<'static, Vec<u16>, Never> {
+        AndThen::new(decode_u8(), |more| if more == 0 {
+            Ready(Vec::new()).boxed()
+        } else {
+            AndThen::new(number(), |n| {
+                map(list(), move |mut rest: Vec<u16>| {
+                    rest.insert(0, n);
+                    rest
+                })
+            })
+                    .boxed()
+        })
+                .boxed()
+    }
+
+    #[test]
+    fn recursive() {
+        let data: &[u8] = &[1, 0, 7, 1, 1, 1, 2, 1, 0, 9, 0, 42];
+        let (data, item, _) = block_on(decode(data, list())).unwrap();
+        assert_eq!(item, vec![7, 258, 9]);
+        assert_eq!(data, &[42]);
+    }
+}
@@ -1,8 +1,12 @@
+use std::marker::PhantomData;
+
 use async_codec::{AsyncDecode, PollDec};
 use async_codec::PollDec::{Done, Progress, Pending, Errored};
 use futures_core::task::Context;
 use futures_io::AsyncRead;
 
+use super::convert_error;
+
 enum State<S, T>
     where S: AsyncDecode
 {
@@ -11,44 +15,69 @@ enum State<S, T>
 }
 
 /// Chain two decoders, running them in sequence.
-pub struct Chain<S, T>(State<S, T>) where S: AsyncDecode;
+///
+/// The errors of both decoders are converted into the error type `E` via `Into`. It defaults to
+/// the error of the first decoder, use `with_error` to choose another one.
+pub struct Chain<S, T, E = <S as AsyncDecode>::Error>
+    where S: AsyncDecode
+{
+    state: State<S, T>,
+    _error: PhantomData<fn() -> E>,
+}
 
 impl<S, T> Chain<S, T>
     where S: AsyncDecode
 {
     /// Create new `Chain` which first decodes via the given `S` and then decodes via the given `T`.
     pub fn new(first: S, second: T) -> Chain<S, T> {
-        Chain(State::First(first, second))
+        Chain {
+            state: State::First(first, second),
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<S, T, E> Chain<S, T, E>
+    where S: AsyncDecode
+{
+    /// Convert the errors of both decoders into `F` instead.
+    pub fn with_error<F>(self) -> Chain<S, T, F> {
+        Chain {
+            state: self.state,
+            _error: PhantomData,
+        }
     }
 }
 
-impl<S, T> AsyncDecode for Chain<S, T>
+impl<S, T, E> AsyncDecode for Chain<S, T, E>
     where S: AsyncDecode,
-          T: AsyncDecode<Error = S::Error>
+          T: AsyncDecode,
+          S::Error: Into<E>,
+          T::Error: Into<E>
 {
     type Item = (S::Item, T::Item);
-    type Error = S::Error;
+    type Error = E;
 
     fn poll_decode<R: AsyncRead>(mut self,
                                  cx: &mut Context,
                                  reader: &mut R)
                                  -> PollDec<Self::Item, Self, Self::Error> {
-        match self.0 {
+        match self.state {
             State::First(first, second) => {
                 match first.poll_decode(cx, reader) {
                     Done(item, read) => {
-                        self.0 = State::Second(second, item);
+                        self.state = State::Second(second, item);
                         Progress(self, read)
                     }
                     Progress(first, read) => {
-                        self.0 = State::First(first, second);
+                        self.state = State::First(first, second);
                         Progress(self, read)
                     }
                     Pending(first) => {
-                        self.0 = State::First(first, second);
+                        self.state = State::First(first, second);
                         Pending(self)
                     }
-                    Errored(err) => Errored(err),
+                    Errored(err) => Errored(convert_error(err)),
                 }
             }
 
@@ -56,14 +85,14 @@ impl<S, T> AsyncDecode for Chain<S, T>
                 match second.poll_decode(cx, reader) {
                     Done(item, read) => Done((first_item, item), read),
                     Progress(second, read) => {
-                        self.0 = State::Second(second, first_item);
+                        self.state = State::Second(second, first_item);
                         Progress(self, read)
                     }
                     Pending(second) => {
-                        self.0 = State::Second(second, first_item);
+                        self.state = State::Second(second, first_item);
                         Pending(self)
                     }
-                    Errored(err) => Errored(err),
+                    Errored(err) => Errored(convert_error(err)),
                 }
             }
         }
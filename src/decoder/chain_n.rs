@@ -0,0 +1,192 @@
+use std::marker::PhantomData;
+
+use async_codec::{AsyncDecode, PollDec};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncRead;
+
+use super::convert_error;
+
+// Poll the decoder at the given index, storing its item once it is done.
+macro_rules! poll_at {
+    ($chain:ident, $cx:ident, $reader:ident, $idx:tt, $n:expr) => {{
+        let dec = $chain.decs.$idx.take().expect("Polled chain after completion");
+        match dec.poll_decode($cx, $reader) {
+            Done(item, read) => {
+                $chain.items.$idx = Some(item);
+                $chain.current += 1;
+                if $chain.current == $n {
+                    Done($chain.into_items(), read)
+                } else {
+                    Progress($chain, read)
+                }
+            }
+            Progress(dec, read) => {
+                $chain.decs.$idx = Some(dec);
+                Progress($chain, read)
+            }
+            Pending(dec) => {
+                $chain.decs.$idx = Some(dec);
+                Pending($chain)
+            }
+            Errored(err) => Errored(convert_error(err)),
+        }
+    }}
+}
+
+macro_rules! chain_n {
+    ($chain:ident, $fn_name:ident, $n:expr, $doc:expr;
+     $A:ident $a:ident;
+     $($T:ident $idx:tt $t:ident),+) => {
+        #[doc = "Chain "]
+        #[doc = $doc]
+        #[doc = " decoders, running them in sequence and yielding a flat tuple of their items."]
+        ///
+        /// The errors of all decoders are converted into the error type `Z` via `Into`. It
+        /// defaults to the error of the first decoder, use `with_error` to choose another one.
+        pub struct $chain<$A, $($T),+, Z = <$A as AsyncDecode>::Error>
+            where $A: AsyncDecode,
+                  $($T: AsyncDecode),+
+        {
+            decs: (Option<$A>, $(Option<$T>),+),
+            items: (Option<$A::Item>, $(Option<$T::Item>),+),
+            current: usize,
+            _error: PhantomData<fn() -> Z>,
+        }
+
+        impl<$A, $($T),+> $chain<$A, $($T),+>
+            where $A: AsyncDecode,
+                  $($T: AsyncDecode),+
+        {
+            #[doc = "Create a new `"]
+            #[doc = stringify!($chain)]
+            #[doc = "`, decoding via the given decoders in order."]
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($a: $A, $($t: $T),+) -> $chain<$A, $($T),+> {
+                $chain {
+                    decs: (Some($a), $(Some($t)),+),
+                    items: (None, $(None::<$T::Item>),+),
+                    current: 0,
+                    _error: PhantomData,
+                }
+            }
+        }
+
+        impl<$A, $($T),+, Z> $chain<$A, $($T),+, Z>
+            where $A: AsyncDecode,
+                  $($T: AsyncDecode),+
+        {
+            /// Convert the errors of all decoders into `Y` instead.
+            pub fn with_error<Y>(self) -> $chain<$A, $($T),+, Y> {
+                $chain {
+                    decs: self.decs,
+                    items: self.items,
+                    current: self.current,
+                    _error: PhantomData,
+                }
+            }
+
+            fn into_items(self) -> ($A::Item, $($T::Item),+) {
+                (self.items.0.unwrap(), $(self.items.$idx.unwrap()),+)
+            }
+        }
+
+        impl<$A, $($T),+, Z> AsyncDecode for $chain<$A, $($T),+, Z>
+            where $A: AsyncDecode,
+                  $($T: AsyncDecode),+,
+                  $A::Error: Into<Z>,
+                  $($T::Error: Into<Z>),+
+        {
+            type Item = ($A::Item, $($T::Item),+);
+            type Error = Z;
+
+            fn poll_decode<R: AsyncRead>(mut self,
+                                         cx: &mut Context,
+                                         reader: &mut R)
+                                         -> PollDec<Self::Item, Self, Self::Error> {
+                match self.current {
+                    0 => poll_at!(self, cx, reader, 0, $n),
+                    $($idx => poll_at!(self, cx, reader, $idx, $n),)+
+                    _ => panic!("Polled chain after completion"),
+                }
+            }
+        }
+
+        #[doc = "Chain "]
+        #[doc = $doc]
+        #[doc = " decoders, running them in sequence and yielding a flat tuple of their items."]
+        #[allow(clippy::too_many_arguments)]
+        pub fn $fn_name<$A, $($T),+>($a: $A, $($t: $T),+) -> $chain<$A, $($T),+>
+            where $A: AsyncDecode,
+                  $($T: AsyncDecode),+
+        {
+            $chain::new($a, $($t),+)
+        }
+    }
+}
+
+chain_n!(Chain3, chain3, 3, "three"; A a; B 1 b, C 2 c);
+chain_n!(Chain4, chain4, 4, "four"; A a; B 1 b, C 2 c, D 3 d);
+chain_n!(Chain5, chain5, 5, "five"; A a; B 1 b, C 2 c, D 3 d, E 4 e);
+chain_n!(Chain6, chain6, 6, "six"; A a; B 1 b, C 2 c, D 3 d, E 4 e, F 5 f);
+chain_n!(Chain7, chain7, 7, "seven"; A a; B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g);
+chain_n!(Chain8, chain8, 8, "eight"; A a; B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h);
+chain_n!(Chain9, chain9, 9, "nine"; A a; B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i);
+chain_n!(Chain10, chain10, 10, "ten";
+         A a; B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i, J 9 j);
+chain_n!(Chain11, chain11, 11, "eleven";
+         A a; B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i, J 9 j, K 10 k);
+chain_n!(Chain12, chain12, 12, "twelve";
+         A a; B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i, J 9 j, K 10 k, L 11 l);
+
+#[cfg(test)]
+mod tests {
+    use async_byteorder::{decode_u16_be, decode_u32_be, decode_u8};
+    use async_codec::DecodeError;
+    use atm_io_utils::partial::*;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::decode;
+    use super::super::super::field::{self, FieldError};
+
+    #[test]
+    fn pending_at_part_boundaries() {
+        let data: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8];
+        let ops = vec![PartialOp::Limited(1), PartialOp::Pending].into_iter().cycle().take(32);
+        let r = PartialRead::new(data, ops);
+        let (_, item, _) =
+            block_on(decode(r, chain4(decode_u8(), decode_u16_be(), decode_u32_be(), decode_u8())))
+                .unwrap();
+        assert_eq!(item, (1, 0x0203, 0x04050607, 8));
+    }
+
+    #[test]
+    fn error_in_middle_part() {
+        let data: &[u8] = &[1, 7, 3];
+        match block_on(decode(data,
+                              chain3(field::decoder::<u8>(),
+                                     field::decoder::<bool>(),
+                                     field::decoder::<u8>()))) {
+            Err((rest, DecodeError::DataError(err))) => {
+                assert_eq!(err, FieldError::InvalidBool(7));
+                assert_eq!(rest, &[3]);
+            }
+            _ => panic!("expected InvalidBool"),
+        }
+    }
+
+    quickcheck! {
+        fn partial_reads(read_ops: Vec<PartialOp>, a: u8, b: u16, c: u32, d: u8) -> bool {
+            let mut data = vec![a];
+            data.extend_from_slice(&[(b >> 8) as u8, b as u8]);
+            data.extend_from_slice(&[(c >> 24) as u8, (c >> 16) as u8, (c >> 8) as u8, c as u8]);
+            data.push(d);
+            let r = PartialRead::new(&data[..], read_ops.into_iter());
+
+            let dec = chain4(decode_u8(), decode_u16_be(), decode_u32_be(), decode_u8());
+            let (_, item, read) = block_on(decode(r, dec)).unwrap();
+            item == (a, b, c, d) && read == data.len()
+        }
+    }
+}
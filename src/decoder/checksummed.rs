@@ -0,0 +1,166 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::{Async, Poll};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+
+use checksum::Checksum;
+
+/// The error of a `Checksummed`.
+#[derive(Debug)]
+pub enum ChecksumError<E> {
+    /// The trailing checksum did not match the checksum of the decoded data.
+    Mismatch,
+    /// The inner decoder errored.
+    Inner(E),
+}
+
+impl<E: Display> Display for ChecksumError<E> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            ChecksumError::Mismatch => write!(f, "Checksum mismatch"),
+            ChecksumError::Inner(ref err) => write!(f, "Checksummed inner error: {}", err),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for ChecksumError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ChecksumError::Mismatch => None,
+            ChecksumError::Inner(ref err) => Some(err),
+        }
+    }
+}
+
+// Feeds all bytes read from the wrapped reader into a checksum.
+struct ChecksumReader<'a, R: 'a, H: 'a> {
+    reader: &'a mut R,
+    checksum: &'a mut H,
+}
+
+impl<'a, R: AsyncRead, H: Checksum> AsyncRead for ChecksumReader<'a, R, H> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        let res = self.reader.poll_read(cx, buf);
+        if let Ok(Async::Ready(read)) = res {
+            self.checksum.update(&buf[..read]);
+        }
+        res
+    }
+}
+
+enum State<D, H, I> {
+    Inner(D, H),
+    // The decoded item, the expected digest and the trailer read so far.
+    Trailer(I, Vec<u8>, Vec<u8>),
+}
+
+/// Wraps a decoder, computing a checksum over all bytes it consumes, and then decodes and verifies
+/// a trailing checksum.
+pub struct Checksummed<D, H>(State<D, H, D::Item>) where D: AsyncDecode;
+
+impl<D, H> Checksummed<D, H>
+    where D: AsyncDecode
+{
+    /// Create a new `Checksummed`, delegating to the given `dec` and verifying the data it consumed
+    /// against a trailing digest of the given `checksum`.
+    pub fn new(dec: D, checksum: H) -> Checksummed<D, H> {
+        Checksummed(State::Inner(dec, checksum))
+    }
+}
+
+impl<D, H> AsyncDecode for Checksummed<D, H>
+    where D: AsyncDecode,
+          H: Checksum
+{
+    type Item = D::Item;
+    type Error = ChecksumError<D::Error>;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        match self.0 {
+            State::Inner(dec, mut checksum) => {
+                let res = dec.poll_decode(cx,
+                                          &mut ChecksumReader {
+                                                   reader,
+                                                   checksum: &mut checksum,
+                                               });
+
+                match res {
+                    Done(item, read) => {
+                        let trailer = Vec::with_capacity(H::LEN);
+                        self.0 = State::Trailer(item, checksum.digest(), trailer);
+                        Progress(self, read)
+                    }
+                    Progress(dec, read) => {
+                        self.0 = State::Inner(dec, checksum);
+                        Progress(self, read)
+                    }
+                    Pending(dec) => {
+                        self.0 = State::Inner(dec, checksum);
+                        Pending(self)
+                    }
+                    Errored(DecodeError::DataError(err)) => {
+                        Errored(DecodeError::DataError(ChecksumError::Inner(err)))
+                    }
+                    Errored(DecodeError::ReaderError(err)) => {
+                        Errored(DecodeError::ReaderError(err))
+                    }
+                }
+            }
+
+            State::Trailer(item, expected, mut trailer) => {
+                let offset = trailer.len();
+                trailer.resize(expected.len(), 0);
+
+                match reader.poll_read(cx, &mut trailer[offset..]) {
+                    Ok(Async::Ready(0)) => {
+                        Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "checksummed").into())
+                    }
+                    Ok(Async::Ready(read)) => {
+                        trailer.truncate(offset + read);
+                        if trailer.len() < expected.len() {
+                            self.0 = State::Trailer(item, expected, trailer);
+                            Progress(self, read)
+                        } else if trailer == expected {
+                            Done(item, read)
+                        } else {
+                            Errored(DecodeError::DataError(ChecksumError::Mismatch))
+                        }
+                    }
+                    Ok(Async::Pending) => {
+                        trailer.truncate(offset);
+                        self.0 = State::Trailer(item, expected, trailer);
+                        Pending(self)
+                    }
+                    Err(err) => Errored(err.into()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_codec::DecodeError;
+    use async_byteorder::decode_u16_be;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::decode;
+    use super::super::super::checksum::Crc32;
+
+    #[test]
+    fn mismatch() {
+        let data: &[u8] = b"\x01\x02\x00\x00\x00\x00";
+        match block_on(decode(data, Checksummed::new(decode_u16_be(), Crc32::new()))) {
+            Err((_, DecodeError::DataError(ChecksumError::Mismatch))) => {}
+            _ => panic!("expected a checksum mismatch"),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::task::Context as TaskContext;
+use futures_io::AsyncRead;
+
+use CodecError;
+use sink::Position;
+
+/// The error of a `Context`: a data error annotated with the field it occurred in and the byte
+/// offset at which decoding of the failing part started.
+///
+/// Nested `Context`s produce a single `ContextError` with the full path to the failing field,
+/// displayed like `at byte 1834, in header.flags: Invalid bool 0x07`.
+#[derive(Debug)]
+pub struct ContextError {
+    offset: usize,
+    absolute: bool,
+    path: Vec<&'static str>,
+    inner: Box<dyn Error + Send + Sync>,
+}
+
+impl ContextError {
+    /// The offset of the poll in which decoding failed.
+    ///
+    /// This is absolute if the failing `Context` or one enclosing it tracks a `sink::Position`,
+    /// otherwise it is relative to the start of the outermost `Context`.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The names of the nested fields in which decoding failed, outermost first.
+    pub fn path(&self) -> &[&'static str] {
+        &self.path
+    }
+
+    /// The error of the decoder that failed.
+    pub fn inner(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.inner
+    }
+
+    /// Return the error of the decoder that failed.
+    pub fn into_inner(self) -> Box<dyn Error + Send + Sync> {
+        self.inner
+    }
+}
+
+impl Display for ContextError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f,
+               "at byte {}, in {}: {}",
+               self.offset,
+               self.path.join("."),
+               self.inner)
+    }
+}
+
+impl Error for ContextError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.inner)
+    }
+}
+
+// Recover the `ContextError` of an inner `Context`, which may have been converted into a
+// `CodecError` on the way.
+fn into_context_error(err: Box<dyn Error + Send + Sync>)
+                      -> Result<ContextError, Box<dyn Error + Send + Sync>> {
+    let err = match err.downcast::<ContextError>() {
+        Ok(err) => return Ok(*err),
+        Err(err) => err,
+    };
+    match err.downcast::<CodecError>() {
+        Ok(err) => {
+            match *err {
+                CodecError::Context(err) => Ok(err),
+                err => Err(Box::new(err)),
+            }
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Wraps a decoder, annotating its data errors with a field name and a byte offset.
+///
+/// Errors of nested `Context`s are merged into a single `ContextError` with the full field path.
+/// Reader errors are passed on unchanged.
+///
+/// Offsets are relative to the start of the outermost `Context`, unless it tracks a
+/// `sink::Position` via `tracking`. Tracking `Context`s report absolute offsets even if their
+/// errors are wrapped by other errors on the way, e.g. inside a `DecompressError::Inner`.
+pub struct Context<D> {
+    dec: D,
+    name: &'static str,
+    offset: usize,
+    position: Option<Position>,
+}
+
+impl<D> Context<D> {
+    /// Create a new `Context`, decoding via `dec` and annotating its errors with `name`.
+    pub fn new(name: &'static str, dec: D) -> Context<D> {
+        Context {
+            dec,
+            name,
+            offset: 0,
+            position: None,
+        }
+    }
+
+    /// Report offsets read from the given `Position` instead of relative ones.
+    pub fn tracking(mut self, position: Position) -> Context<D> {
+        self.position = Some(position);
+        self
+    }
+}
+
+impl<D> AsyncDecode for Context<D>
+    where D: AsyncDecode,
+          D::Error: Into<Box<dyn Error + Send + Sync>>
+{
+    type Item = D::Item;
+    type Error = ContextError;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut TaskContext,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        let offset = match self.position {
+            Some(ref position) => position.get(),
+            None => self.offset,
+        };
+
+        match self.dec.poll_decode(cx, reader) {
+            Done(item, read) => Done(item, read),
+            Progress(dec, read) => {
+                self.dec = dec;
+                self.offset += read;
+                Progress(self, read)
+            }
+            Pending(dec) => {
+                self.dec = dec;
+                Pending(self)
+            }
+            Errored(DecodeError::ReaderError(err)) => Errored(DecodeError::ReaderError(err)),
+            Errored(DecodeError::DataError(err)) => {
+                // A relative offset of an inner context is replaced by ours, which has seen all
+                // bytes the inner context has seen, so it is at least as absolute.
+                let absolute = self.position.is_some();
+                let err = match into_context_error(err.into()) {
+                    Ok(mut err) => {
+                        err.path.insert(0, self.name);
+                        if !err.absolute {
+                            err.offset = offset;
+                            err.absolute = absolute;
+                        }
+                        err
+                    }
+                    Err(inner) => {
+                        ContextError {
+                            offset,
+                            absolute,
+                            path: vec![self.name],
+                            inner,
+                        }
+                    }
+                };
+                Errored(DecodeError::DataError(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_byteorder::decode_u8;
+    use async_codec::DecodeError;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::decode;
+    use super::super::super::decoder::{chain, chain3, tee, Aligned, AlignedError};
+    use super::super::super::field::{self, Be, DecodeField, FieldError};
+
+    fn header() -> Context<impl AsyncDecode<Item = (u8, u32, bool), Error = ContextError>> {
+        Context::new("header",
+                     chain3(Context::new("version", field::decoder::<u8>()),
+                            Context::new("length", <u32 as DecodeField<Be>>::field_decoder()),
+                            Context::new("flags", field::decoder::<bool>())))
+    }
+
+    #[test]
+    fn path_and_offset() {
+        let data: &[u8] = &[1, 0, 0, 0, 2, 1];
+        let (_, item, _) = block_on(decode(data, header())).unwrap();
+        assert_eq!(item, (1, 2, true));
+
+        let data: &[u8] = &[1, 0, 0, 0, 2, 7];
+        match block_on(decode(data, header())) {
+            Err((_, DecodeError::DataError(err))) => {
+                assert_eq!(err.offset(), 5);
+                assert_eq!(err.path(), &["header", "flags"]);
+                assert_eq!(err.inner().downcast_ref::<FieldError>(),
+                           Some(&FieldError::InvalidBool(7)));
+                assert_eq!(err.to_string(), "at byte 5, in header.flags: Invalid bool 0x07");
+            }
+            _ => panic!("expected ContextError"),
+        }
+
+        // A tracking context reports absolute offsets, even if the reader has been partially
+        // consumed by a previous driver and its error is wrapped in another error.
+        let data: &[u8] = &[0xff, 0xff, 1, 0, 0, 0, 2, 7];
+        let (rest, _, read) = block_on(decode(data, chain(decode_u8(), decode_u8()))).unwrap();
+        let position = Position::new(read);
+        let dec = Aligned::new(header().tracking(position.clone()), 4);
+        match block_on(decode(rest, tee(dec, position))) {
+            Err((_, DecodeError::DataError(AlignedError::Inner(err)))) => {
+                assert_eq!(err.offset(), 7);
+                assert_eq!(err.path(), &["header", "flags"]);
+            }
+            _ => panic!("expected ContextError"),
+        }
+
+        let data: &[u8] = &[1, 0];
+        match block_on(decode(data, header())) {
+            Err((_, DecodeError::ReaderError(_))) => {}
+            _ => panic!("expected ReaderError"),
+        }
+    }
+}
@@ -31,15 +31,8 @@ impl<E: Display, I> Display for DecodeExactError<E, I> {
     }
 }
 
-impl<E: Error, I: Debug> Error for DecodeExactError<E, I> {
-    fn description(&self) -> &str {
-        match *self {
-            DecodeExactError::Early(_, _) => "decoder finished early",
-            DecodeExactError::Inner(ref err) => err.description(),
-        }
-    }
-
-    fn cause(&self) -> Option<&Error> {
+impl<E: Error + 'static, I: Debug> Error for DecodeExactError<E, I> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
         match *self {
             DecodeExactError::Early(_, _) => None,
             DecodeExactError::Inner(ref err) => Some(err),
@@ -0,0 +1,324 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::{Async, Poll};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+
+use compression::Decompressor;
+
+const CHUNK: usize = 8 * 1024;
+
+/// The error of a `Decompressed`.
+#[derive(Debug)]
+pub enum DecompressError<E> {
+    /// The compressed data was invalid.
+    Corrupt(FutIoErr),
+    /// The decompressed data exceeded the contained maximum size.
+    TooLarge(usize),
+    /// The inner decoder finished before all decompressed data was consumed, or the compressed
+    /// stream ended before all data read from the reader was consumed.
+    TrailingData,
+    /// The inner decoder errored.
+    Inner(E),
+}
+
+impl<E: Display> Display for DecompressError<E> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            DecompressError::Corrupt(ref err) => write!(f, "Corrupt compressed data: {}", err),
+            DecompressError::TooLarge(max_len) => {
+                write!(f, "Decompressed data exceeds maximum of {} bytes", max_len)
+            }
+            DecompressError::TrailingData => write!(f, "Trailing data after decompressed item"),
+            DecompressError::Inner(ref err) => write!(f, "Decompressed inner error: {}", err),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for DecompressError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            DecompressError::Corrupt(ref err) => Some(err),
+            DecompressError::Inner(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Reads from a chunk of data buffered by a decoder wrapping another one, e.g. decompressed or
+/// decrypted from the actual reader.
+///
+/// Once the chunk is used up, reads return `Pending` without registering a wakeup, unless the
+/// chunk ends the data. So an inner decoder reading from it waits for the next chunk instead of
+/// seeing the end of the data. If the inner decoder returns `Pending` and `starved` is true, the
+/// wrapping decoder must obtain the next chunk and poll it again, or else return `Pending` only
+/// after the actual reader did.
+pub struct ChunkReader<'a> {
+    chunk: &'a [u8],
+    end: bool,
+    starved: bool,
+}
+
+impl<'a> ChunkReader<'a> {
+    /// Create a new `ChunkReader` reading from `chunk`, signalling the end of the data once it is
+    /// used up if `end` is true.
+    pub fn new(chunk: &'a [u8], end: bool) -> ChunkReader<'a> {
+        ChunkReader {
+            chunk,
+            end,
+            starved: false,
+        }
+    }
+
+    /// The number of bytes of the chunk that have not been read yet.
+    pub fn remaining(&self) -> usize {
+        self.chunk.len()
+    }
+
+    /// Whether a read returned `Pending` because the chunk was used up.
+    pub fn starved(&self) -> bool {
+        self.starved
+    }
+}
+
+impl<'a> AsyncRead for ChunkReader<'a> {
+    fn poll_read(&mut self, _: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        if self.chunk.is_empty() && !self.end && !buf.is_empty() {
+            self.starved = true;
+            return Ok(Async::Pending);
+        }
+
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk[..len]);
+        self.chunk = &self.chunk[len..];
+        Ok(Async::Ready(len))
+    }
+}
+
+enum State<D, I> {
+    Decoding(D),
+    // The inner decoder is done, the rest of the compressed stream must not contain any data.
+    Finishing(I),
+}
+
+/// Wraps a decoder and presents it with the decompressed data read from the reader.
+///
+/// This reads compressed data in chunks, so it must only be used on readers that end with the
+/// compressed stream, e.g. by wrapping it in a `DecodeExact` of the compressed length.
+pub struct Decompressed<D, Z>
+    where D: AsyncDecode
+{
+    state: State<D, D::Item>,
+    decompressor: Z,
+    max_len: usize,
+    decompressed: usize,
+    // Compressed data, of which `input[in_offset..in_len]` has not been decompressed yet.
+    input: Vec<u8>,
+    in_offset: usize,
+    in_len: usize,
+    // Decompressed data, of which `output[out_offset..out_len]` has not been decoded yet.
+    output: Vec<u8>,
+    out_offset: usize,
+    out_len: usize,
+    stream_end: bool,
+}
+
+impl<D, Z> Decompressed<D, Z>
+    where D: AsyncDecode
+{
+    /// Create a new `Decompressed`, decoding the data decompressed by `decompressor` via `dec`,
+    /// and erroring if there are more than `max_len` bytes of decompressed data.
+    pub fn new(dec: D, decompressor: Z, max_len: usize) -> Decompressed<D, Z> {
+        Decompressed {
+            state: State::Decoding(dec),
+            decompressor,
+            max_len,
+            decompressed: 0,
+            input: vec![0; CHUNK],
+            in_offset: 0,
+            in_len: 0,
+            output: vec![0; CHUNK],
+            out_offset: 0,
+            out_len: 0,
+            stream_end: false,
+        }
+    }
+}
+
+impl<D, Z> AsyncDecode for Decompressed<D, Z>
+    where D: AsyncDecode,
+          Z: Decompressor
+{
+    type Item = D::Item;
+    type Error = DecompressError<D::Error>;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        // The number of bytes read from `reader`, it is only polled once.
+        let mut read = None;
+
+        loop {
+            if self.out_offset < self.out_len || self.stream_end {
+                match self.state {
+                    State::Decoding(dec) => {
+                        let output = &self.output[self.out_offset..self.out_len];
+                        let mut chunk = ChunkReader::new(output, self.stream_end);
+                        let res = dec.poll_decode(cx, &mut chunk);
+                        self.out_offset = self.out_len - chunk.remaining();
+
+                        match res {
+                            Done(item, _) => self.state = State::Finishing(item),
+                            Progress(dec, _) => self.state = State::Decoding(dec),
+                            // The inner decoder waits for more decompressed data.
+                            Pending(dec) if chunk.starved() => self.state = State::Decoding(dec),
+                            Pending(dec) => {
+                                self.state = State::Decoding(dec);
+                                return match read {
+                                           Some(read) => Progress(self, read),
+                                           None => Pending(self),
+                                       };
+                            }
+                            Errored(DecodeError::DataError(err)) => {
+                                return Errored(DecodeError::DataError(DecompressError::Inner(err)))
+                            }
+                            Errored(DecodeError::ReaderError(err)) => {
+                                return Errored(DecodeError::ReaderError(err))
+                            }
+                        }
+                        continue;
+                    }
+
+                    State::Finishing(item) => {
+                        if self.out_offset < self.out_len ||
+                           (self.stream_end && self.in_offset < self.in_len) {
+                            return Errored(DecodeError::DataError(DecompressError::TrailingData));
+                        } else if self.stream_end {
+                            return Done(item, read.unwrap_or(0));
+                        } else {
+                            self.state = State::Finishing(item);
+                        }
+                    }
+                }
+            }
+
+            // There is no decompressed data, so try to decompress some.
+            let step = match self.decompressor
+                      .decompress(&self.input[self.in_offset..self.in_len], &mut self.output) {
+                Ok(step) => step,
+                Err(err) => return Errored(DecodeError::DataError(DecompressError::Corrupt(err))),
+            };
+            self.in_offset += step.consumed;
+            self.out_offset = 0;
+            self.out_len = step.produced;
+            self.stream_end = step.done;
+            self.decompressed += step.produced;
+
+            if self.decompressed > self.max_len {
+                return Errored(DecodeError::DataError(DecompressError::TooLarge(self.max_len)));
+            }
+            if step.consumed > 0 || step.produced > 0 || step.done {
+                continue;
+            }
+
+            // The decompressor needs more input.
+            if let Some(read) = read {
+                return Progress(self, read);
+            }
+
+            self.input.copy_within(self.in_offset..self.in_len, 0);
+            self.in_len -= self.in_offset;
+            self.in_offset = 0;
+            match reader.poll_read(cx, &mut self.input[self.in_len..]) {
+                Ok(Async::Ready(0)) => {
+                    return Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "decompressed").into())
+                }
+                Ok(Async::Ready(n)) => {
+                    self.in_len += n;
+                    read = Some(n);
+                }
+                Ok(Async::Pending) => return Pending(self),
+                Err(err) => return Errored(err.into()),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "flate2"))]
+mod tests {
+    use async_codec::DecodeError;
+    use async_ringbuffer::ring_buffer;
+    use atm_io_utils::partial::*;
+    use flate2::{Compress, Compression, FlushCompress};
+    use futures_core::Never;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::decode;
+    use super::super::super::decoder::{self, until_delimiter};
+    use super::super::super::encoder;
+    use super::super::super::compression::{DeflateCompressor, DeflateDecompressor};
+    use super::super::super::testing::{check_wakeups, test_codec, GreedyDecoder};
+
+    // Data spanning several chunks of decompressed output, without any `0xff` bytes.
+    fn payload(extra: usize) -> Vec<u8> {
+        (0..3 * CHUNK + extra).map(|i| (((i % 251) ^ (i / 997)) & 0x7f) as u8).collect()
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut compressed = vec![0; data.len() + 1024];
+        let mut compress = Compress::new(Compression::best(), false);
+        compress.compress(data, &mut compressed, FlushCompress::Finish).unwrap();
+        compressed.truncate(compress.total_out() as usize);
+        compressed
+    }
+
+    fn greedy(len: usize) -> impl AsyncDecode<Item = Vec<u8>, Error = DecompressError<Never>> {
+        Decompressed::new(GreedyDecoder::new(len), DeflateDecompressor::new(false), len)
+    }
+
+    #[test]
+    fn bomb() {
+        let compressed = compress(&[0; 100_000]);
+
+        let dec = Decompressed::new(until_delimiter(b"\n", 200_000),
+                                    DeflateDecompressor::new(false),
+                                    1000);
+        match block_on(decode(&compressed[..], dec)) {
+            Err((_, DecodeError::DataError(DecompressError::TooLarge(1000)))) => {}
+            _ => panic!("expected a TooLarge error"),
+        }
+    }
+
+    #[test]
+    fn repeated_reads() {
+        let data = payload(17);
+        let compressed = compress(&data);
+
+        let (_, item, read) = block_on(decode(&compressed[..], greedy(data.len()))).unwrap();
+        assert_eq!((item, read), (data.clone(), compressed.len()));
+        check_wakeups(|| greedy(data.len()), &compressed);
+    }
+
+    quickcheck! {
+        fn partial_reads(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, extra: u16) -> bool {
+            let data = payload(extra as usize);
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let enc = encoder::until_delimiter(data.clone(), b"\xff").unwrap();
+            let enc = encoder::compressed(enc, DeflateCompressor::new(6, false));
+            let dec = decoder::chain(GreedyDecoder::new(data.len()), decoder::skip(1));
+            let dec = Decompressed::new(dec, DeflateDecompressor::new(false), data.len() + 1);
+            let test_outcome = test_codec(r, w, dec, enc);
+            test_outcome.1 && (test_outcome.0).0 == data
+        }
+    }
+}
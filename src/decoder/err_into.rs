@@ -0,0 +1,50 @@
+use std::marker::PhantomData;
+
+use async_codec::{AsyncDecode, PollDec};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncRead;
+
+use super::convert_error;
+
+/// Change the error type of a decoder by converting its errors via `Into`.
+pub struct ErrInto<D, E> {
+    dec: D,
+    _error: PhantomData<fn() -> E>,
+}
+
+impl<D, E> ErrInto<D, E> {
+    /// Create a new `ErrInto`, delegating to the given `dec` and converting its errors into `E`.
+    pub fn new(dec: D) -> ErrInto<D, E> {
+        ErrInto {
+            dec,
+            _error: PhantomData,
+        }
+    }
+}
+
+impl<D, E> AsyncDecode for ErrInto<D, E>
+    where D: AsyncDecode,
+          D::Error: Into<E>
+{
+    type Item = D::Item;
+    type Error = E;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        match self.dec.poll_decode(cx, reader) {
+            Done(item, read) => Done(item, read),
+            Progress(dec, read) => {
+                self.dec = dec;
+                Progress(self, read)
+            }
+            Pending(dec) => {
+                self.dec = dec;
+                Pending(self)
+            }
+            Errored(err) => Errored(convert_error(err)),
+        }
+    }
+}
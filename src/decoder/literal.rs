@@ -0,0 +1,137 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::Async::{Ready, Pending as AsyncPending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+
+/// The data read by a `Literal` differed from the expected bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiteralMismatch {
+    /// The offset of the first differing byte, relative to the start of the literal.
+    pub offset: usize,
+    /// The byte that was expected at that offset.
+    pub expected: u8,
+    /// The byte that was read instead.
+    pub found: u8,
+}
+
+impl Display for LiteralMismatch {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f,
+               "Expected byte {:#04x} at offset {}, found {:#04x}",
+               self.expected,
+               self.offset,
+               self.found)
+    }
+}
+
+impl Error for LiteralMismatch {}
+
+/// Decodes exactly the given bytes, e.g. a magic number, erroring on the first byte that differs.
+pub struct Literal {
+    bytes: &'static [u8],
+    offset: usize,
+}
+
+impl Literal {
+    /// Create a new `Literal`, expecting exactly the given `bytes`.
+    pub fn new(bytes: &'static [u8]) -> Literal {
+        Literal { bytes, offset: 0 }
+    }
+}
+
+impl AsyncDecode for Literal {
+    type Item = ();
+    type Error = LiteralMismatch;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        if self.offset == self.bytes.len() {
+            return Done((), 0);
+        }
+
+        let mut buf = [0u8; 64];
+        let len = buf.len().min(self.bytes.len() - self.offset);
+
+        match reader.poll_read(cx, &mut buf[..len]) {
+            Ok(Ready(0)) => Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "literal").into()),
+            Ok(Ready(read)) => {
+                let expected = &self.bytes[self.offset..self.offset + read];
+                if let Some(i) = (0..read).find(|&i| buf[i] != expected[i]) {
+                    return Errored(DecodeError::DataError(LiteralMismatch {
+                                                              offset: self.offset + i,
+                                                              expected: expected[i],
+                                                              found: buf[i],
+                                                          }));
+                }
+
+                self.offset += read;
+                if self.offset == self.bytes.len() {
+                    Done((), read)
+                } else {
+                    Progress(self, read)
+                }
+            }
+            Ok(AsyncPending) => Pending(self),
+            Err(err) => Errored(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_codec::DecodeError;
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::{decode, CodecError};
+    use super::super::super::testing::{test_codec_len, unexpected_eof_errors};
+    use super::super::super::decoder::{self, Skip};
+    use super::super::super::encoder;
+
+    #[test]
+    fn mismatch() {
+        let data: &[u8] = b"\x89PNH\r\n";
+        match block_on(decode(data, Literal::new(b"\x89PNG\r\n"))) {
+            Err((_, DecodeError::DataError(err))) => {
+                assert_eq!(err,
+                           LiteralMismatch {
+                               offset: 3,
+                               expected: b'G',
+                               found: b'H',
+                           })
+            }
+            _ => panic!("expected LiteralMismatch"),
+        }
+
+        let data: &[u8] = b"\x89PN";
+        assert!(unexpected_eof_errors(data, Literal::new(b"\x89PNG")));
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, reserved: u16) -> bool {
+            let reserved = reserved as usize;
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let dec = decoder::chain3(decoder::literal(b"MAGIC"),
+                                      Skip::new(reserved),
+                                      decoder::literal(b"END"))
+                    .with_error::<CodecError>();
+            let enc = encoder::chain3(encoder::literal(b"MAGIC"),
+                                      encoder::zeros(reserved),
+                                      encoder::literal(b"END"));
+            test_codec_len(r, w, dec, enc).1
+        }
+    }
+}
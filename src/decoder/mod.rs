@@ -1,24 +1,144 @@
 //! Utilities for working with decores.
 
-use async_codec::AsyncDecode;
+use async_codec::{AsyncDecode, DecodeError};
 
+mod aligned;
+pub use self::aligned::{Aligned, AlignedError};
 mod and_then;
 pub use self::and_then::AndThen;
+mod context;
+pub use self::context::{Context, ContextError};
 mod decode_exact;
 pub use self::decode_exact::{DecodeExact, DecodeExactError};
+mod err_into;
+pub use self::err_into::ErrInto;
+mod literal;
+pub use self::literal::{Literal, LiteralMismatch};
 mod map;
 pub use self::map::Map;
+mod boxed;
+pub use self::boxed::{BoxDecoder, BoxedDecode};
 mod chain;
 pub use self::chain::Chain;
+mod chain_n;
+pub use self::chain_n::{Chain3, Chain4, Chain5, Chain6, Chain7, Chain8, Chain9, Chain10, Chain11, Chain12,
+                        chain3, chain4, chain5, chain6, chain7, chain8, chain9, chain10, chain11, chain12};
+mod checksummed;
+pub use self::checksummed::{Checksummed, ChecksumError};
+mod decompressed;
+pub use self::decompressed::{ChunkReader, Decompressed, DecompressError};
+mod skip;
+pub use self::skip::{Skip, SkipUntil};
+mod tee;
+pub use self::tee::Tee;
+mod until_delimiter;
+pub use self::until_delimiter::{UntilDelimiter, UntilDelimiterError};
+mod or;
+pub use self::or::{Or, OrError, RecordReader, ReplayReader};
+mod recursive;
+pub use self::recursive::{Recursive, Recursion, DepthExceeded};
+#[cfg(feature = "serde")]
+mod serde_bincode;
+#[cfg(feature = "serde")]
+pub use self::serde_bincode::{SerdeBincode, SerdeBincodeError, DEFAULT_MAX_LEN};
 
 /// Chain a compution on the result of a decoder.
 pub fn map<D, F>(decoder: D, f: F) -> Map<D, F> {
     Map::new(decoder, f)
 }
 
+/// Change the error type of a decoder by converting its errors into `E`.
+pub fn err_into<E, D>(decoder: D) -> ErrInto<D, E> {
+    ErrInto::new(decoder)
+}
+
+// Convert the data error of a `DecodeError`, leaving reader errors untouched.
+fn convert_error<E: Into<F>, F>(err: DecodeError<E>) -> DecodeError<F> {
+    match err {
+        DecodeError::ReaderError(err) => DecodeError::ReaderError(err),
+        DecodeError::DataError(err) => DecodeError::DataError(err.into()),
+    }
+}
+
 /// Create new `Chain` which first decodes via the given `S` and then decodes via the given `T`.
 pub fn chain<S, T>(first: S, second: T) -> Chain<S, T>
     where S: AsyncDecode
 {
     Chain::new(first, second)
 }
+
+/// Wrap a decoder, annotating its data errors with the field `name` and a byte offset.
+pub fn context<D>(name: &'static str, dec: D) -> Context<D> {
+    Context::new(name, dec)
+}
+
+/// Create a new `Literal`, decoding exactly the given `bytes` and erroring on the first byte that
+/// differs.
+pub fn literal(bytes: &'static [u8]) -> Literal {
+    Literal::new(bytes)
+}
+
+/// Create a new `Skip`, discarding the next `n` bytes.
+pub fn skip(n: usize) -> Skip {
+    Skip::new(n)
+}
+
+/// Create a new `SkipUntil`, discarding everything up to and including the next occurence of
+/// `delim`.
+pub fn skip_until(delim: &[u8]) -> SkipUntil {
+    SkipUntil::new(delim)
+}
+
+/// Wrap a decoder, skipping padding after it up to the next multiple of `alignment`.
+pub fn aligned<D: AsyncDecode>(dec: D, alignment: usize) -> Aligned<D> {
+    Aligned::new(dec, alignment)
+}
+
+/// Create a new `UntilDelimiter`, decoding everything up to the next occurence of `delim`, and
+/// erroring if more than `max_len` bytes precede the delimiter.
+pub fn until_delimiter(delim: &[u8], max_len: usize) -> UntilDelimiter {
+    UntilDelimiter::new(delim, max_len)
+}
+
+/// Wrap a decoder, verifying the bytes it consumes against a trailing digest of the given
+/// checksum.
+pub fn checksummed<D, H>(dec: D, checksum: H) -> Checksummed<D, H>
+    where D: AsyncDecode
+{
+    Checksummed::new(dec, checksum)
+}
+
+/// Wrap a decoder, feeding all bytes it consumes into the given sink, which is yielded together
+/// with the decoded item.
+pub fn tee<D, S>(dec: D, sink: S) -> Tee<D, S> {
+    Tee::new(dec, sink)
+}
+
+/// Wrap a decoder, presenting it with the data decompressed by the given decompressor, and
+/// erroring if there are more than `max_len` bytes of decompressed data.
+pub fn decompressed<D, Z>(dec: D, decompressor: Z, max_len: usize) -> Decompressed<D, Z>
+    where D: AsyncDecode
+{
+    Decompressed::new(dec, decompressor, max_len)
+}
+
+/// Try to decode via `first`, falling back to decoding the same bytes via `second` if `first`
+/// rejects the data, buffering at most `max_backtrack` bytes.
+pub fn or<A, B>(first: A, second: B, max_backtrack: usize) -> Or<A, B>
+    where A: AsyncDecode
+{
+    Or::new(first, second, max_backtrack)
+}
+
+/// Create a decoder that can refer to itself, see `Recursive::new`.
+pub fn recursive<'a, Item, E, F>(max_depth: usize, f: F) -> Recursive<'a, Item, E>
+    where F: Fn(Recursion<'a, Item, E>) -> BoxDecoder<'a, Item, E> + 'a
+{
+    Recursive::new(max_depth, f)
+}
+
+/// Create a decoder for a serde value, encoded via `encoder::serde_bincode`.
+#[cfg(feature = "serde")]
+pub fn serde_bincode<T: ::serde::de::DeserializeOwned>() -> SerdeBincode<T> {
+    SerdeBincode::new()
+}
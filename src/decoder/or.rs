@@ -0,0 +1,378 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::Async::Ready;
+use futures_core::Poll;
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+
+/// The error of an `Or`.
+#[derive(Debug)]
+pub enum OrError<A, B> {
+    /// The first decoder read more than the contained number of bytes without finishing, so it
+    /// could no longer be backtracked.
+    BacktrackExceeded(usize),
+    /// Both decoders rejected the data.
+    Both(A, B),
+    /// The second decoder finished without consuming the contained number of bytes that had been
+    /// read by the first decoder. These bytes can not be handed back to the reader.
+    Leftover(usize),
+}
+
+impl<A: Display, B: Display> Display for OrError<A, B> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            OrError::BacktrackExceeded(max) => {
+                write!(f, "Exceeded maximum backtrack buffer of {} bytes", max)
+            }
+            OrError::Both(ref a, ref b) => {
+                write!(f, "Both alternatives failed, first: {}, second: {}", a, b)
+            }
+            OrError::Leftover(len) => {
+                write!(f, "Second alternative left {} backtracked bytes unconsumed", len)
+            }
+        }
+    }
+}
+
+/// The source of `Both` is the error of the first decoder, the second one is only part of the
+/// message.
+impl<A: Error + 'static, B: Error> Error for OrError<A, B> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            OrError::Both(ref a, _) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+/// Records all bytes read through it into a buffer, for later replay via a `ReplayReader`.
+///
+/// As decoders are polled with a fresh reader each time, this borrows the buffer, which has to be
+/// kept in the decoder's state. At most `max` bytes are recorded, reading more errors with an io
+/// error of kind `Other`, after which `exceeded` returns true.
+pub struct RecordReader<'a, R: 'a> {
+    inner: &'a mut R,
+    recorded: &'a mut Vec<u8>,
+    max: usize,
+    exceeded: bool,
+}
+
+impl<'a, R> RecordReader<'a, R> {
+    /// Create a new `RecordReader`, reading from `inner` and appending to `recorded` until it
+    /// holds `max` bytes.
+    pub fn new(inner: &'a mut R, recorded: &'a mut Vec<u8>, max: usize) -> RecordReader<'a, R> {
+        RecordReader {
+            inner,
+            recorded,
+            max,
+            exceeded: false,
+        }
+    }
+
+    /// Whether a read failed because the buffer was full.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+impl<'a, R: AsyncRead> AsyncRead for RecordReader<'a, R> {
+    // `io::Error::other` would need Rust 1.74.
+    #[allow(clippy::io_other_error)]
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        let capacity = self.max - self.recorded.len();
+        if capacity == 0 && !buf.is_empty() {
+            self.exceeded = true;
+            return Err(FutIoErr::new(ErrorKind::Other, "backtrack buffer exceeded"));
+        }
+
+        let len = buf.len().min(capacity);
+        match self.inner.poll_read(cx, &mut buf[..len]) {
+            Ok(Ready(read)) => {
+                self.recorded.extend_from_slice(&buf[..read]);
+                Ok(Ready(read))
+            }
+            other => other,
+        }
+    }
+}
+
+/// Serves previously recorded bytes before reading from the inner reader.
+///
+/// The position within the recorded bytes is borrowed, so that it can be kept in the decoder's
+/// state across polls.
+pub struct ReplayReader<'a, R: 'a> {
+    inner: &'a mut R,
+    recorded: &'a [u8],
+    offset: &'a mut usize,
+    fresh: usize,
+}
+
+impl<'a, R> ReplayReader<'a, R> {
+    /// Create a new `ReplayReader`, serving `recorded` starting at `offset`, and reading from
+    /// `inner` afterwards.
+    pub fn new(inner: &'a mut R, recorded: &'a [u8], offset: &'a mut usize) -> ReplayReader<'a, R> {
+        ReplayReader {
+            inner,
+            recorded,
+            offset,
+            fresh: 0,
+        }
+    }
+
+    /// The number of bytes read from the inner reader rather than replayed.
+    pub fn fresh(&self) -> usize {
+        self.fresh
+    }
+
+    /// The number of recorded bytes that have not been replayed yet.
+    pub fn remaining(&self) -> usize {
+        self.recorded.len() - *self.offset
+    }
+}
+
+impl<'a, R: AsyncRead> AsyncRead for ReplayReader<'a, R> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        if *self.offset < self.recorded.len() {
+            let len = buf.len().min(self.recorded.len() - *self.offset);
+            buf[..len].copy_from_slice(&self.recorded[*self.offset..*self.offset + len]);
+            *self.offset += len;
+            return Ok(Ready(len));
+        }
+
+        match self.inner.poll_read(cx, buf) {
+            Ok(Ready(read)) => {
+                self.fresh += read;
+                Ok(Ready(read))
+            }
+            other => other,
+        }
+    }
+}
+
+enum State<A: AsyncDecode, B> {
+    First(A, B),
+    Second(B, A::Error, usize),
+}
+
+/// Tries to decode via `A`, and if that fails with a `DataError`, decodes the same bytes via `B`.
+///
+/// The bytes read by `A` are buffered so that they can be replayed to `B`. At most
+/// `max_backtrack` bytes are buffered, `A` erroring with `BacktrackExceeded` if it reads more
+/// than that without finishing. `B` has to consume all bytes read by `A`, otherwise this errors
+/// with `Leftover`.
+pub struct Or<A: AsyncDecode, B> {
+    state: State<A, B>,
+    recorded: Vec<u8>,
+    max_backtrack: usize,
+}
+
+impl<A: AsyncDecode, B> Or<A, B> {
+    /// Create a new `Or`, trying `first` and falling back to `second`, buffering at most
+    /// `max_backtrack` bytes.
+    pub fn new(first: A, second: B, max_backtrack: usize) -> Or<A, B> {
+        Or {
+            state: State::First(first, second),
+            recorded: Vec::new(),
+            max_backtrack,
+        }
+    }
+}
+
+impl<A, B> AsyncDecode for Or<A, B>
+    where A: AsyncDecode,
+          B: AsyncDecode<Item = A::Item>
+{
+    type Item = A::Item;
+    type Error = OrError<A::Error, B::Error>;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        match self.state {
+            State::First(first, second) => {
+                let old_len = self.recorded.len();
+                let mut record_reader =
+                    RecordReader::new(reader, &mut self.recorded, self.max_backtrack);
+
+                match first.poll_decode(cx, &mut record_reader) {
+                    Done(item, read) => Done(item, read),
+                    Progress(first, read) => {
+                        self.state = State::First(first, second);
+                        Progress(self, read)
+                    }
+                    Pending(first) => {
+                        self.state = State::First(first, second);
+                        Pending(self)
+                    }
+                    Errored(DecodeError::ReaderError(_)) if record_reader.exceeded() => {
+                        let err = OrError::BacktrackExceeded(self.max_backtrack);
+                        Errored(DecodeError::DataError(err))
+                    }
+                    Errored(DecodeError::ReaderError(err)) => {
+                        Errored(DecodeError::ReaderError(err))
+                    }
+                    Errored(DecodeError::DataError(err)) => {
+                        // Bytes read from the reader during this poll must still be reported.
+                        let read = self.recorded.len() - old_len;
+                        self.state = State::Second(second, err, 0);
+                        if read > 0 {
+                            Progress(self, read)
+                        } else {
+                            self.poll_decode(cx, reader)
+                        }
+                    }
+                }
+            }
+
+            State::Second(mut second, first_err, mut offset) => {
+                // Replayed bytes have already been reported, so keep polling until `second` reads
+                // fresh bytes, to avoid reporting long runs of zero progress.
+                loop {
+                    let old_offset = offset;
+                    let (res, fresh, remaining) = {
+                        let mut replay_reader = ReplayReader::new(reader,
+                                                                  &self.recorded,
+                                                                  &mut offset);
+                        let res = second.poll_decode(cx, &mut replay_reader);
+                        (res, replay_reader.fresh(), replay_reader.remaining())
+                    };
+
+                    return match res {
+                        Done(_, _) if remaining > 0 => {
+                            Errored(DecodeError::DataError(OrError::Leftover(remaining)))
+                        }
+                        Done(item, _) => Done(item, fresh),
+                        Progress(new_second, _) => {
+                            if fresh == 0 && offset > old_offset {
+                                second = new_second;
+                                continue;
+                            }
+                            self.state = State::Second(new_second, first_err, offset);
+                            Progress(self, fresh)
+                        }
+                        Pending(second) => {
+                            self.state = State::Second(second, first_err, offset);
+                            Pending(self)
+                        }
+                        Errored(DecodeError::ReaderError(err)) => {
+                            Errored(DecodeError::ReaderError(err))
+                        }
+                        Errored(DecodeError::DataError(err)) => {
+                            Errored(DecodeError::DataError(OrError::Both(first_err, err)))
+                        }
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use async_codec::DecodeError;
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use futures_executor::block_on;
+    use quickcheck::TestResult;
+
+    use async_byteorder::decode_u8;
+
+    use super::OrError;
+    use super::super::super::{decode, MAX_IDLE_PROGRESS};
+    use super::super::super::testing::test_codec;
+    use super::super::super::decoder::{literal, map, or, until_delimiter as dec_until_delimiter,
+                                       LiteralMismatch};
+    use super::super::super::encoder::until_delimiter as enc_until_delimiter;
+
+    #[test]
+    fn alternatives() {
+        let dec = || or(dec_until_delimiter(b";", 4), dec_until_delimiter(b"!", 16), 8);
+
+        let data: &[u8] = b"ab;cd!";
+        let (rest, item, _) = block_on(decode(data, dec())).unwrap();
+        assert_eq!((item, rest), (b"ab".to_vec(), &b"cd!"[..]));
+
+        let data: &[u8] = b"hello!world";
+        let (rest, item, _) = block_on(decode(data, dec())).unwrap();
+        assert_eq!((item, rest), (b"hello".to_vec(), &b"world"[..]));
+
+        let data: &[u8] = b"hello world, how are you?";
+        match block_on(decode(data, dec())) {
+            Err((_, DecodeError::DataError(OrError::Both(_, _)))) => {}
+            _ => panic!(),
+        }
+
+        let data: &[u8] = b"hello!";
+        let dec = or(dec_until_delimiter(b";", 4), dec_until_delimiter(b"!", 16), 3);
+        match block_on(decode(data, dec)) {
+            Err((_, DecodeError::DataError(OrError::BacktrackExceeded(3)))) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn long_replay() {
+        // The second alternative reads the replayed bytes one at a time.
+        let len = MAX_IDLE_PROGRESS + 100;
+        let mut data = vec![b'a'; len];
+        data.extend_from_slice(b"!rest");
+
+        let dec = or(dec_until_delimiter(b";", len - 1), dec_until_delimiter(b"!", 2 * len), len);
+        let (rest, item, read) = block_on(decode(&data[..], dec)).unwrap();
+        assert_eq!((item.len(), rest, read), (len, &b"rest"[..], len + 1));
+    }
+
+    #[test]
+    fn leftover() {
+        let data: &[u8] = b"abXY";
+        let dec = or(map(literal(b"abcd"), |_| 0u8), decode_u8(), 16);
+        match block_on(decode(data, dec)) {
+            Err((_, DecodeError::DataError(OrError::Leftover(3)))) => {}
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn both_source() {
+        let data: &[u8] = b"xy";
+        let dec = or(literal(b"ab"), literal(b"xz"), 16);
+        match block_on(decode(data, dec)) {
+            Err((_, DecodeError::DataError(err))) => {
+                let source = err.source().unwrap().downcast_ref::<LiteralMismatch>().unwrap();
+                assert_eq!(source.offset, 0);
+            }
+            _ => panic!(),
+        }
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, data: Vec<u8>) -> TestResult {
+            if data.contains(&b';') {
+                return TestResult::discard();
+            }
+            let enc = match enc_until_delimiter(data.clone(), b"!") {
+                Ok(enc) => enc,
+                Err(_) => return TestResult::discard(),
+            };
+
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            // The first alternative always rejects the data before reaching the delimiter.
+            let dec = or(dec_until_delimiter(b";", data.len() / 2),
+                         dec_until_delimiter(b"!", data.len()),
+                         data.len() + 1);
+            let test_outcome = test_codec(r, w, dec, enc);
+            TestResult::from_bool(test_outcome.1 && test_outcome.0 == data)
+        }
+    }
+}
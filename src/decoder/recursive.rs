@@ -0,0 +1,254 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncRead;
+
+use super::BoxDecoder;
+
+/// Signals that a `Recursive` decoder nested deeper than the contained maximum depth.
+///
+/// The error type of a recursive decoder must be convertible from this.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthExceeded(pub usize);
+
+impl Display for DepthExceeded {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "Nesting exceeds maximum depth of {}", self.0)
+    }
+}
+
+impl Error for DepthExceeded {}
+
+type Builder<'a, Item, E> = dyn Fn(Recursion<'a, Item, E>) -> BoxDecoder<'a, Item, E> + 'a;
+
+/// A handle through which a recursive decoder refers to itself.
+pub struct Recursion<'a, Item, E> {
+    f: Rc<Builder<'a, Item, E>>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'a, Item, E> Clone for Recursion<'a, Item, E> {
+    fn clone(&self) -> Recursion<'a, Item, E> {
+        Recursion {
+            f: self.f.clone(),
+            depth: self.depth,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+impl<'a, Item, E> Recursion<'a, Item, E> {
+    /// Create a decoder for a nested occurence of the recursive decoder.
+    ///
+    /// This is cheap, the nested decoder is only constructed once it is polled.
+    pub fn decoder(&self) -> Recursive<'a, Item, E> {
+        Recursive(State::Start(Recursion {
+                                   f: self.f.clone(),
+                                   depth: self.depth + 1,
+                                   max_depth: self.max_depth,
+                               }))
+    }
+
+    /// The nesting depth of the decoder this handle was passed to, starting at 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+enum State<'a, Item, E> {
+    Start(Recursion<'a, Item, E>),
+    Running(BoxDecoder<'a, Item, E>),
+}
+
+/// A decoder defined in terms of itself, created via `decoder::recursive`.
+pub struct Recursive<'a, Item, E>(State<'a, Item, E>);
+
+impl<'a, Item, E> Recursive<'a, Item, E> {
+    /// Create a new `Recursive`, obtaining the actual decoder by calling `f` with a handle to
+    /// create nested occurences of itself. Errors once more than `max_depth` occurences are
+    /// nested within each other.
+    pub fn new<F>(max_depth: usize, f: F) -> Recursive<'a, Item, E>
+        where F: Fn(Recursion<'a, Item, E>) -> BoxDecoder<'a, Item, E> + 'a
+    {
+        Recursive(State::Start(Recursion {
+                                   f: Rc::new(f),
+                                   depth: 0,
+                                   max_depth,
+                               }))
+    }
+}
+
+impl<'a, Item, E> AsyncDecode for Recursive<'a, Item, E>
+    where E: From<DepthExceeded>
+{
+    type Item = Item;
+    type Error = E;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        let dec = match self.0 {
+            State::Start(rec) => {
+                if rec.depth > rec.max_depth {
+                    return Errored(DecodeError::DataError(DepthExceeded(rec.max_depth).into()));
+                }
+                (rec.f)(rec.clone())
+            }
+            State::Running(dec) => dec,
+        };
+
+        match dec.poll_decode(cx, reader) {
+            Done(item, read) => Done(item, read),
+            Progress(dec, read) => {
+                self.0 = State::Running(dec);
+                Progress(self, read)
+            }
+            Pending(dec) => {
+                self.0 = State::Running(dec);
+                Pending(self)
+            }
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use async_codec::{AsyncDecode, DecodeError, PollDec};
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use futures_core::Async::{Ready, Pending};
+    use futures_core::task::Context;
+    use futures_executor::block_on;
+    use futures_io::AsyncRead;
+    use futures_io::ErrorKind::UnexpectedEof;
+
+    use super::super::super::{decode, encode};
+    use super::super::super::testing::test_codec;
+    use super::super::{self as decoder, AndThen, BoxDecoder, BoxedDecode, DepthExceeded,
+                       Recursion};
+    use super::super::super::encoder::{self, BoxEncoder, BoxedEncode};
+    use super::super::super::encoder::Recursion as EncRecursion;
+    use async_byteorder::encode_u8;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Tree(u8, Vec<Tree>);
+
+    #[derive(Debug, PartialEq)]
+    enum TreeError {
+        TooDeep(usize),
+    }
+
+    impl From<DepthExceeded> for TreeError {
+        fn from(err: DepthExceeded) -> TreeError {
+            TreeError::TooDeep(err.0)
+        }
+    }
+
+    // Decodes a single byte, or yields the contained byte without reading anything.
+    enum Byte {
+        Read,
+        Ready(u8),
+    }
+
+    impl AsyncDecode for Byte {
+        type Item = u8;
+        type Error = TreeError;
+
+        fn poll_decode<R: AsyncRead>(self,
+                                     cx: &mut Context,
+                                     reader: &mut R)
+                                     -> PollDec<Self::Item, Self, Self::Error> {
+            if let Byte::Ready(item) = self {
+                return PollDec::Done(item, 0);
+            }
+
+            let mut buf = [0];
+            match reader.poll_read(cx, &mut buf) {
+                Ok(Ready(0)) => PollDec::Errored(DecodeError::ReaderError(UnexpectedEof.into())),
+                Ok(Ready(_)) => PollDec::Done(buf[0], 1),
+                Ok(Pending) => PollDec::Pending(self),
+                Err(err) => PollDec::Errored(DecodeError::ReaderError(err)),
+            }
+        }
+    }
+
+    fn children(rec: Recursion<'static, Tree, TreeError>,
+                count: u8)
+                -> BoxDecoder<'static, Vec<Tree>, TreeError> {
+        if count == 0 {
+            return decoder::map(Byte::Ready(0), |_| Vec::new()).boxed();
+        }
+
+        AndThen::new(rec.decoder(), move |child| {
+            decoder::map(children(rec, count - 1), move |mut rest: Vec<Tree>| {
+                rest.insert(0, child);
+                rest
+            })
+        })
+                .boxed()
+    }
+
+    // A tree is a value byte, the number of children and then the children.
+    fn tree_decoder(max_depth: usize) -> decoder::Recursive<'static, Tree, TreeError> {
+        decoder::recursive(max_depth, |rec| {
+            AndThen::new(decoder::chain(Byte::Read, Byte::Read),
+                         move |(value, count)| {
+                             decoder::map(children(rec, count), move |kids| Tree(value, kids))
+                         })
+                    .boxed()
+        })
+    }
+
+    fn tree_encoder(max_depth: usize, tree: Tree) -> encoder::Recursive<BoxEncoder<'static>> {
+        encoder::recursive(max_depth, tree, |rec: EncRecursion<Tree>, Tree(value, kids)| {
+            let count = kids.len() as u8;
+            let head: BoxEncoder = encoder::chain(encode_u8(value), encode_u8(count)).boxed();
+            kids.into_iter()
+                .fold(head, |enc, kid| encoder::chain(enc, rec.encoder(kid)).boxed())
+        })
+    }
+
+    fn sample() -> Tree {
+        Tree(1,
+             vec![Tree(2, vec![]),
+                  Tree(3, vec![Tree(4, vec![Tree(5, vec![])]), Tree(6, vec![])])])
+    }
+
+    #[test]
+    fn too_deep() {
+        let (writer, _) = block_on(encode(Cursor::new(Vec::new()), tree_encoder(3, sample())))
+            .unwrap();
+        let data = writer.into_inner();
+
+        let (_, tree, _) = block_on(decode(&data[..], tree_decoder(3))).unwrap();
+        assert_eq!(tree, sample());
+
+        match block_on(decode(&data[..], tree_decoder(2))) {
+            Err((_, DecodeError::DataError(err))) => assert_eq!(err, TreeError::TooDeep(2)),
+            _ => panic!(),
+        }
+        assert!(block_on(encode(Cursor::new(Vec::new()), tree_encoder(2, sample()))).is_err());
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let test_outcome = test_codec(r, w, tree_decoder(3), tree_encoder(3, sample()));
+            test_outcome.1 && test_outcome.0 == sample()
+        }
+    }
+}
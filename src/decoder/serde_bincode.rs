@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use bincode::{self, Options};
+use futures_core::Async::{Ready, Pending as AsyncPending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+use serde::de::DeserializeOwned;
+
+use field::{DecodeField, FieldError, Varint, VarintDecoder};
+
+/// The maximum length of an encoded value accepted by default, 16 MiB.
+pub const DEFAULT_MAX_LEN: usize = 16 * 1024 * 1024;
+
+/// The error of a `SerdeBincode` decoder.
+#[derive(Debug)]
+pub enum SerdeBincodeError {
+    /// The length prefix was not a valid varint.
+    Length(FieldError),
+    /// The length prefix exceeded the contained maximum.
+    TooLong(usize),
+    /// Bincode failed to deserialize the value.
+    Bincode(bincode::Error),
+}
+
+impl Display for SerdeBincodeError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            SerdeBincodeError::Length(ref err) => write!(f, "Invalid length prefix: {}", err),
+            SerdeBincodeError::TooLong(max_len) => {
+                write!(f, "Encoded value exceeds maximum of {} bytes", max_len)
+            }
+            SerdeBincodeError::Bincode(ref err) => write!(f, "Bincode error: {}", err),
+        }
+    }
+}
+
+impl Error for SerdeBincodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SerdeBincodeError::Length(ref err) => Some(err),
+            SerdeBincodeError::TooLong(_) => None,
+            SerdeBincodeError::Bincode(ref err) => Some(err),
+        }
+    }
+}
+
+/// Decodes a serde value from its bincode encoding, prefixed by its length as a varint.
+///
+/// The value has to span the whole length, any trailing bytes are reported as a
+/// `SerdeBincodeError::Bincode`.
+///
+/// This never reads past the end of the encoding, so any decoder running afterwards sees the bytes
+/// following it.
+pub struct SerdeBincode<T> {
+    // `None` once the length prefix has been decoded.
+    len: Option<VarintDecoder<usize>>,
+    max_len: usize,
+    remaining: usize,
+    buf: Vec<u8>,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T> SerdeBincode<T> {
+    /// Create a new `SerdeBincode`, accepting encodings of up to `DEFAULT_MAX_LEN` bytes.
+    pub fn new() -> SerdeBincode<T> {
+        SerdeBincode {
+            len: Some(<usize as DecodeField<Varint>>::field_decoder()),
+            max_len: DEFAULT_MAX_LEN,
+            remaining: 0,
+            buf: Vec::new(),
+            _item: PhantomData,
+        }
+    }
+
+    /// Error if the encoding of the value is longer than `max_len` bytes.
+    pub fn max_len(mut self, max_len: usize) -> SerdeBincode<T> {
+        self.max_len = max_len;
+        self
+    }
+
+    fn deserialize(&self) -> Result<T, DecodeError<SerdeBincodeError>>
+        where T: DeserializeOwned
+    {
+        // The same encoding as `bincode::serialize`, but the value has to span the whole frame.
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .reject_trailing_bytes()
+            .deserialize(&self.buf)
+            .map_err(|err| DecodeError::DataError(SerdeBincodeError::Bincode(err)))
+    }
+}
+
+impl<T> Default for SerdeBincode<T> {
+    fn default() -> SerdeBincode<T> {
+        SerdeBincode::new()
+    }
+}
+
+impl<T: DeserializeOwned> AsyncDecode for SerdeBincode<T> {
+    type Item = T;
+    type Error = SerdeBincodeError;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        if let Some(len) = self.len.take() {
+            return match len.poll_decode(cx, reader) {
+                       Done(len, read) => {
+                           if len > self.max_len {
+                               let err = SerdeBincodeError::TooLong(self.max_len);
+                               Errored(DecodeError::DataError(err))
+                           } else if len == 0 {
+                               match self.deserialize() {
+                                   Ok(item) => Done(item, read),
+                                   Err(err) => Errored(err),
+                               }
+                           } else {
+                               self.remaining = len;
+                               Progress(self, read)
+                           }
+                       }
+                       Progress(len, read) => {
+                           self.len = Some(len);
+                           Progress(self, read)
+                       }
+                       Pending(len) => {
+                           self.len = Some(len);
+                           Pending(self)
+                       }
+                       Errored(DecodeError::ReaderError(err)) => {
+                           Errored(DecodeError::ReaderError(err))
+                       }
+                       Errored(DecodeError::DataError(err)) => {
+                           Errored(DecodeError::DataError(SerdeBincodeError::Length(err)))
+                       }
+                   };
+        }
+
+        // Grow the buffer as data arrives, rather than trusting the length up front.
+        let mut chunk = [0; 1024];
+        let chunk_len = self.remaining.min(chunk.len());
+        match reader.poll_read(cx, &mut chunk[..chunk_len]) {
+            Ok(Ready(0)) => {
+                Errored(DecodeError::ReaderError(FutIoErr::new(ErrorKind::UnexpectedEof,
+                                                               "serde bincode")))
+            }
+            Ok(Ready(read)) => {
+                self.buf.extend_from_slice(&chunk[..read]);
+                self.remaining -= read;
+                if self.remaining == 0 {
+                    match self.deserialize() {
+                        Ok(item) => Done(item, read),
+                        Err(err) => Errored(err),
+                    }
+                } else {
+                    Progress(self, read)
+                }
+            }
+            Ok(AsyncPending) => Pending(self),
+            Err(err) => Errored(DecodeError::ReaderError(err)),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use async_codec::{AsyncDecode, PollDec};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::Async::{Ready, Pending as AsyncPending};
+use futures_core::Never;
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+
+/// Discards a fixed number of bytes, without allocating.
+///
+/// This never errors with a `DataError`. Its error `Never` converts into `CodecError`, so it can
+/// be chained with other decoders via `Chain::with_error` or `err_into`.
+pub struct Skip {
+    remaining: usize,
+}
+
+impl Skip {
+    /// Create a new `Skip`, discarding the next `n` bytes.
+    pub fn new(n: usize) -> Skip {
+        Skip { remaining: n }
+    }
+}
+
+impl AsyncDecode for Skip {
+    type Item = ();
+    type Error = Never;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        if self.remaining == 0 {
+            return Done((), 0);
+        }
+
+        let mut buf = [0u8; 256];
+        let len = buf.len().min(self.remaining);
+
+        match reader.poll_read(cx, &mut buf[..len]) {
+            Ok(Ready(0)) => Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "skip").into()),
+            Ok(Ready(read)) => {
+                self.remaining -= read;
+                if self.remaining == 0 {
+                    Done((), read)
+                } else {
+                    Progress(self, read)
+                }
+            }
+            Ok(AsyncPending) => Pending(self),
+            Err(err) => Errored(err.into()),
+        }
+    }
+}
+
+/// Discards all bytes up to and including the next occurence of a delimiter.
+///
+/// This never reads past the delimiter, and only allocates a buffer of the delimiter's size once.
+/// Like `Skip`, it never errors with a `DataError`.
+pub struct SkipUntil {
+    delim: Vec<u8>,
+    // For each prefix of the delimiter, the length of its longest proper suffix that is also a
+    // prefix of the delimiter.
+    fallback: Vec<usize>,
+    matched: usize,
+    buf: Vec<u8>,
+}
+
+impl SkipUntil {
+    /// Create a new `SkipUntil`, discarding everything up to and including the next occurence of
+    /// `delim`.
+    ///
+    /// Panics if `delim` is empty.
+    pub fn new(delim: &[u8]) -> SkipUntil {
+        assert!(!delim.is_empty(), "Delimiter must not be empty");
+
+        let mut fallback = vec![0; delim.len()];
+        let mut k = 0;
+        for i in 1..delim.len() {
+            while k > 0 && delim[i] != delim[k] {
+                k = fallback[k - 1];
+            }
+            if delim[i] == delim[k] {
+                k += 1;
+            }
+            fallback[i] = k;
+        }
+
+        SkipUntil {
+            delim: delim.to_vec(),
+            fallback,
+            matched: 0,
+            buf: vec![0; delim.len()],
+        }
+    }
+
+    fn feed(&mut self, byte: u8) {
+        while self.matched > 0 && self.delim[self.matched] != byte {
+            self.matched = self.fallback[self.matched - 1];
+        }
+        if self.delim[self.matched] == byte {
+            self.matched += 1;
+        }
+    }
+}
+
+impl AsyncDecode for SkipUntil {
+    type Item = ();
+    type Error = Never;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        // The delimiter can not be completed with fewer bytes than this, so reading them all at
+        // once never consumes anything past it.
+        let needed = self.delim.len() - self.matched;
+
+        match reader.poll_read(cx, &mut self.buf[..needed]) {
+            Ok(Ready(0)) => Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "skip_until").into()),
+            Ok(Ready(read)) => {
+                for i in 0..read {
+                    let byte = self.buf[i];
+                    self.feed(byte);
+                }
+
+                if self.matched == self.delim.len() {
+                    Done((), read)
+                } else {
+                    Progress(self, read)
+                }
+            }
+            Ok(AsyncPending) => Pending(self),
+            Err(err) => Errored(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::decode;
+    use super::super::super::testing::unexpected_eof_errors;
+
+    #[test]
+    fn skip() {
+        let data: &[u8] = &[0; 1000];
+        let (rest, _, read) = block_on(decode(data, Skip::new(998))).unwrap();
+        assert_eq!((rest.len(), read), (2, 998));
+
+        assert!(unexpected_eof_errors(&data[..3], Skip::new(4)));
+    }
+
+    #[test]
+    fn skip_until() {
+        let data: &[u8] = b"xxababcyz";
+        let (rest, _, read) = block_on(decode(data, SkipUntil::new(b"abc"))).unwrap();
+        assert_eq!((rest, read), (&b"yz"[..], 7));
+
+        let data: &[u8] = b"aaabaab";
+        let (rest, _, _) = block_on(decode(data, SkipUntil::new(b"aab"))).unwrap();
+        assert_eq!(rest, b"aab");
+
+        let data: &[u8] = b"ababab";
+        assert!(unexpected_eof_errors(data, SkipUntil::new(b"abc")));
+    }
+}
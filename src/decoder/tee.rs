@@ -0,0 +1,94 @@
+use async_codec::{AsyncDecode, PollDec};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::{Async, Poll};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr};
+
+use sink::ByteSink;
+
+// Feeds all bytes read from the wrapped reader into a sink.
+struct TeeReader<'a, R: 'a, S: 'a> {
+    reader: &'a mut R,
+    sink: &'a mut S,
+}
+
+impl<'a, R: AsyncRead, S: ByteSink> AsyncRead for TeeReader<'a, R, S> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        let res = self.reader.poll_read(cx, buf);
+        if let Ok(Async::Ready(read)) = res {
+            self.sink.consume(&buf[..read]);
+        }
+        res
+    }
+}
+
+/// Wraps a decoder and feeds all bytes it consumes into a `ByteSink`, yielding the sink together
+/// with the decoded item.
+pub struct Tee<D, S> {
+    dec: D,
+    sink: S,
+}
+
+impl<D, S> Tee<D, S> {
+    /// Create a new `Tee`, delegating to the given `dec` and feeding the bytes it consumes into the
+    /// given `sink`.
+    pub fn new(dec: D, sink: S) -> Tee<D, S> {
+        Tee { dec, sink }
+    }
+}
+
+impl<D, S> AsyncDecode for Tee<D, S>
+    where D: AsyncDecode,
+          S: ByteSink
+{
+    type Item = (D::Item, S);
+    type Error = D::Error;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        let res = self.dec
+            .poll_decode(cx,
+                         &mut TeeReader {
+                                  reader,
+                                  sink: &mut self.sink,
+                              });
+
+        match res {
+            Done(item, read) => Done((item, self.sink), read),
+            Progress(dec, read) => {
+                self.dec = dec;
+                Progress(self, read)
+            }
+            Pending(dec) => {
+                self.dec = dec;
+                Pending(self)
+            }
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "digest"))]
+mod tests {
+    use async_byteorder::decode_u32_be;
+    use futures_executor::block_on;
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use super::super::super::decode;
+    use super::super::super::sink::DigestSink;
+
+    #[test]
+    fn sha256() {
+        let data: &[u8] = b"\x01\x02\x03\x04\x05";
+        let (rest, (item, sink), _) =
+            block_on(decode(data, Tee::new(decode_u32_be(), DigestSink(Sha256::new()))))
+                .map_err(|(_, err)| err)
+                .unwrap();
+        assert_eq!(item, 0x01020304);
+        assert_eq!(rest, b"\x05");
+        assert_eq!(sink.0.finalize(), Sha256::digest(&data[..4]));
+    }
+}
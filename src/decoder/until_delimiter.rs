@@ -0,0 +1,186 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, PollDec, DecodeError};
+use async_codec::PollDec::{Done, Progress, Pending, Errored};
+use futures_core::Async::{Ready, Pending as AsyncPending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+
+/// The error of an `UntilDelimiter`.
+#[derive(Debug)]
+pub enum UntilDelimiterError {
+    /// No delimiter was found within the contained maximum number of bytes.
+    TooLong(usize),
+}
+
+impl Display for UntilDelimiterError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            UntilDelimiterError::TooLong(max_len) => {
+                write!(f, "No delimiter found within {} bytes", max_len)
+            }
+        }
+    }
+}
+
+impl Error for UntilDelimiterError {}
+
+/// Decodes all bytes up to a delimiter, yielding them without the delimiter.
+///
+/// This never reads past the delimiter, so any decoder running afterwards sees the bytes following
+/// it.
+pub struct UntilDelimiter {
+    delim: Vec<u8>,
+    max_len: usize,
+    crlf: bool,
+    buf: Vec<u8>,
+}
+
+impl UntilDelimiter {
+    /// Create a new `UntilDelimiter`, decoding everything up to the next occurence of `delim`,
+    /// and erroring if more than `max_len` bytes precede the delimiter.
+    ///
+    /// Panics if `delim` is empty.
+    pub fn new(delim: &[u8], max_len: usize) -> UntilDelimiter {
+        assert!(!delim.is_empty(), "Delimiter must not be empty");
+
+        UntilDelimiter {
+            delim: delim.to_vec(),
+            max_len,
+            crlf: false,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Also drop a `'\r'` directly preceding the delimiter from the decoded bytes.
+    ///
+    /// With a delimiter of `b"\n"`, this accepts both `\n` and `\r\n` line endings. The
+    /// dropped byte does not count towards the maximum length.
+    pub fn crlf(mut self) -> UntilDelimiter {
+        self.crlf = true;
+        self
+    }
+
+    // The length of the longest proper prefix of the delimiter the buffer ends with.
+    fn overlap(&self) -> usize {
+        let mut k = self.buf.len().min(self.delim.len() - 1);
+        while k > 0 && !self.buf.ends_with(&self.delim[..k]) {
+            k -= 1;
+        }
+        k
+    }
+
+    fn limit(&self) -> usize {
+        if self.crlf {
+            self.max_len + 1
+        } else {
+            self.max_len
+        }
+    }
+}
+
+impl AsyncDecode for UntilDelimiter {
+    type Item = Vec<u8>;
+    type Error = UntilDelimiterError;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        // A delimiter can not be completed with fewer bytes than this, so reading them all at
+        // once never consumes anything past it.
+        let old_len = self.buf.len();
+        let needed = self.delim.len() - self.overlap();
+        self.buf.resize(old_len + needed, 0);
+
+        match reader.poll_read(cx, &mut self.buf[old_len..]) {
+            Ok(Ready(0)) => {
+                Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "until_delimiter").into())
+            }
+            Ok(Ready(read)) => {
+                self.buf.truncate(old_len + read);
+
+                if self.buf.ends_with(&self.delim) {
+                    let len = self.buf.len() - self.delim.len();
+                    self.buf.truncate(len);
+                    if self.crlf && self.buf.last() == Some(&b'\r') {
+                        self.buf.pop();
+                    }
+
+                    if self.buf.len() > self.max_len {
+                        Errored(DecodeError::DataError(UntilDelimiterError::TooLong(self.max_len)))
+                    } else {
+                        Done(self.buf, read)
+                    }
+                } else if self.buf.len() - self.overlap() > self.limit() {
+                    Errored(DecodeError::DataError(UntilDelimiterError::TooLong(self.max_len)))
+                } else {
+                    Progress(self, read)
+                }
+            }
+            Ok(AsyncPending) => {
+                self.buf.truncate(old_len);
+                Pending(self)
+            }
+            Err(err) => Errored(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_codec::DecodeError;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::super::decode;
+    use super::super::super::testing::unexpected_eof_errors;
+
+    #[test]
+    fn straddling_delimiter() {
+        let data: &[u8] = b"ab\r\ra\r\r\nxyz";
+        let (rest, item, read) = block_on(decode(data, UntilDelimiter::new(b"\r\r\n", 16)))
+            .map_err(|(_, err)| err)
+            .unwrap();
+        assert_eq!(item, b"ab\r\ra");
+        assert_eq!(read, 8);
+        assert_eq!(rest, b"xyz");
+    }
+
+    #[test]
+    fn crlf() {
+        let data: &[u8] = b"abc\r\nde\nf";
+        let (rest, first, _) = block_on(decode(data, UntilDelimiter::new(b"\n", 3).crlf()))
+            .map_err(|(_, err)| err)
+            .unwrap();
+        let (_, second, _) = block_on(decode(rest, UntilDelimiter::new(b"\n", 3).crlf()))
+            .map_err(|(_, err)| err)
+            .unwrap();
+        assert_eq!(first, b"abc");
+        assert_eq!(second, b"de");
+    }
+
+    #[test]
+    fn too_long() {
+        let data: &[u8] = b"abcd\n";
+        match block_on(decode(data, UntilDelimiter::new(b"\n", 3))) {
+            Err((_, DecodeError::DataError(UntilDelimiterError::TooLong(3)))) => {}
+            _ => panic!("expected TooLong error"),
+        }
+
+        let data: &[u8] = b"abcdefgh";
+        match block_on(decode(data, UntilDelimiter::new(b"\n", 3))) {
+            Err((rest, DecodeError::DataError(UntilDelimiterError::TooLong(3)))) => {
+                assert_eq!(rest, b"efgh")
+            }
+            _ => panic!("expected TooLong error"),
+        }
+    }
+
+    #[test]
+    fn eof() {
+        let data: &[u8] = b"abc";
+        assert!(unexpected_eof_errors(data, UntilDelimiter::new(b"\n", 16)));
+    }
+}
@@ -0,0 +1,110 @@
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncWrite;
+
+use super::Zeros;
+
+// The number of bytes needed to advance `offset` to a multiple of `alignment`.
+fn padding(alignment: usize, offset: usize) -> usize {
+    (alignment - offset % alignment) % alignment
+}
+
+enum State<C> {
+    Inner(C),
+    Padding(Zeros),
+}
+
+/// Wraps an encoder, writing zero bytes after it until the absolute offset is a multiple of some
+/// alignment.
+///
+/// The offset at which the `Aligned` starts is fixed, so that `remaining_bytes` is exact. It is 0
+/// unless set via `at_offset`, e.g. to the summed `remaining_bytes` of the preceding encoders, or
+/// to the value of a `sink::Position` fed by an `encoder::Tee` when the `Aligned` is created by
+/// an `encoder::Then`.
+pub struct Aligned<C> {
+    state: State<C>,
+    alignment: usize,
+    offset: usize,
+}
+
+impl<C> Aligned<C> {
+    /// Create a new `Aligned`, encoding via `enc` and then padding up to the next multiple of
+    /// `alignment`.
+    ///
+    /// Panics if `alignment` is zero.
+    pub fn new(enc: C, alignment: usize) -> Aligned<C> {
+        assert!(alignment > 0, "Alignment must not be zero");
+
+        Aligned {
+            state: State::Inner(enc),
+            alignment,
+            offset: 0,
+        }
+    }
+
+    /// Set the absolute offset at which the inner encoder starts.
+    pub fn at_offset(mut self, offset: usize) -> Aligned<C> {
+        self.offset = offset;
+        self
+    }
+}
+
+impl<C: AsyncEncode> AsyncEncode for Aligned<C> {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        match self.state {
+            State::Inner(enc) => {
+                match enc.poll_encode(cx, writer) {
+                    Done(written) => {
+                        self.offset += written;
+                        let padding = padding(self.alignment, self.offset);
+                        if padding == 0 {
+                            Done(written)
+                        } else {
+                            self.state = State::Padding(Zeros::new(padding));
+                            Progress(self, written)
+                        }
+                    }
+                    Progress(enc, written) => {
+                        self.offset += written;
+                        self.state = State::Inner(enc);
+                        Progress(self, written)
+                    }
+                    Pending(enc) => {
+                        self.state = State::Inner(enc);
+                        Pending(self)
+                    }
+                    Errored(err) => Errored(err),
+                }
+            }
+
+            State::Padding(zeros) => {
+                match zeros.poll_encode(cx, writer) {
+                    Done(written) => Done(written),
+                    Progress(zeros, written) => {
+                        self.offset += written;
+                        self.state = State::Padding(zeros);
+                        Progress(self, written)
+                    }
+                    Pending(zeros) => {
+                        self.state = State::Padding(zeros);
+                        Pending(self)
+                    }
+                    Errored(err) => Errored(err),
+                }
+            }
+        }
+    }
+}
+
+impl<C: AsyncEncodeLen> AsyncEncodeLen for Aligned<C> {
+    fn remaining_bytes(&self) -> usize {
+        match self.state {
+            State::Inner(ref enc) => {
+                let remaining = enc.remaining_bytes();
+                remaining + padding(self.alignment, self.offset + remaining)
+            }
+            State::Padding(ref zeros) => zeros.remaining_bytes(),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncWrite;
+
+// Object-safe version of `AsyncEncode`, polling in place rather than by value.
+trait DynEncode {
+    fn poll_encode_dyn(&mut self, cx: &mut Context, writer: &mut dyn AsyncWrite) -> PollEnc<()>;
+}
+
+// Object-safe version of `AsyncEncodeLen`.
+trait DynEncodeLen: DynEncode {
+    fn remaining_bytes_dyn(&self) -> usize;
+}
+
+// Holds an encoder between polls, `None` once it is done.
+struct Slot<C>(Option<C>);
+
+impl<C: AsyncEncode> DynEncode for Slot<C> {
+    fn poll_encode_dyn(&mut self,
+                       cx: &mut Context,
+                       mut writer: &mut dyn AsyncWrite)
+                       -> PollEnc<()> {
+        let enc = self.0.take().expect("Polled BoxEncoder after completion");
+        match enc.poll_encode(cx, &mut writer) {
+            Done(written) => Done(written),
+            Progress(enc, written) => {
+                self.0 = Some(enc);
+                Progress((), written)
+            }
+            Pending(enc) => {
+                self.0 = Some(enc);
+                Pending(())
+            }
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+impl<C: AsyncEncodeLen> DynEncodeLen for Slot<C> {
+    fn remaining_bytes_dyn(&self) -> usize {
+        self.0.as_ref().map_or(0, |enc| enc.remaining_bytes())
+    }
+}
+
+/// A type-erased encoder, allowing encoders of different types to be used interchangeably.
+pub struct BoxEncoder<'a>(Box<dyn DynEncode + 'a>);
+
+impl<'a> BoxEncoder<'a> {
+    /// Create a new `BoxEncoder`, erasing the type of the given encoder.
+    pub fn new<C: AsyncEncode + 'a>(enc: C) -> BoxEncoder<'a> {
+        BoxEncoder(Box::new(Slot(Some(enc))))
+    }
+}
+
+impl<'a> AsyncEncode for BoxEncoder<'a> {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        match self.0.poll_encode_dyn(cx, writer) {
+            Done(written) => Done(written),
+            Progress((), written) => Progress(self, written),
+            Pending(()) => Pending(self),
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+/// A type-erased encoder that knows how many bytes it is going to write.
+pub struct BoxEncoderLen<'a>(Box<dyn DynEncodeLen + 'a>);
+
+impl<'a> BoxEncoderLen<'a> {
+    /// Create a new `BoxEncoderLen`, erasing the type of the given encoder.
+    pub fn new<C: AsyncEncodeLen + 'a>(enc: C) -> BoxEncoderLen<'a> {
+        BoxEncoderLen(Box::new(Slot(Some(enc))))
+    }
+}
+
+impl<'a> AsyncEncode for BoxEncoderLen<'a> {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        match self.0.poll_encode_dyn(cx, writer) {
+            Done(written) => Done(written),
+            Progress((), written) => Progress(self, written),
+            Pending(()) => Pending(self),
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+impl<'a> AsyncEncodeLen for BoxEncoderLen<'a> {
+    fn remaining_bytes(&self) -> usize {
+        self.0.remaining_bytes_dyn()
+    }
+}
+
+/// Adds `boxed` to all encoders.
+pub trait BoxedEncode: AsyncEncode + Sized {
+    /// Erase the type of this encoder by turning it into a `BoxEncoder`.
+    fn boxed<'a>(self) -> BoxEncoder<'a>
+        where Self: 'a
+    {
+        BoxEncoder::new(self)
+    }
+
+    /// Erase the type of this encoder by turning it into a `BoxEncoderLen`.
+    fn boxed_len<'a>(self) -> BoxEncoderLen<'a>
+        where Self: AsyncEncodeLen + 'a
+    {
+        BoxEncoderLen::new(self)
+    }
+}
+
+impl<C: AsyncEncode> BoxedEncode for C {}
+
+#[cfg(test)]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+
+    use async_byteorder::{decode_u8, decode_u16_be, encode_u8, encode_u16_be};
+    use super::super::super::testing::test_codec_len;
+    use super::super::super::decoder::{self, AndThen, BoxedDecode};
+    use super::super::{self as encoder, BoxEncoderLen, BoxedEncode};
+
+    // Encodes small numbers in a single byte, others in a tagged u16.
+    fn number(n: u16) -> BoxEncoderLen<'static> {
+        if n < 256 {
+            encoder::chain(encode_u8(0), encode_u8(n as u8)).boxed_len()
+        } else {
+            encoder::chain(encode_u8(1), encode_u16_be(n)).boxed_len()
+        }
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, a: u16, b: u16) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let number_dec = || AndThen::new(decode_u8(), |tag| if tag == 0 {
+                decoder::map(decode_u8(), u16::from).boxed()
+            } else {
+                decode_u16_be().boxed()
+            });
+            let dec = decoder::chain(number_dec(), number_dec());
+            let enc = encoder::chain(number(a), number(b));
+            let test_outcome = test_codec_len(r, w, dec, enc);
+            test_outcome.1 && test_outcome.0 == (a, b)
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncWrite;
+
+// Poll the encoder at the given index.
+macro_rules! poll_at {
+    ($chain:ident, $cx:ident, $writer:ident, $idx:tt, $n:expr) => {{
+        let enc = $chain.encs.$idx.take().expect("Polled chain after completion");
+        match enc.poll_encode($cx, $writer) {
+            Done(written) => {
+                $chain.current += 1;
+                if $chain.current == $n {
+                    Done(written)
+                } else {
+                    Progress($chain, written)
+                }
+            }
+            Progress(enc, written) => {
+                $chain.encs.$idx = Some(enc);
+                Progress($chain, written)
+            }
+            Pending(enc) => {
+                $chain.encs.$idx = Some(enc);
+                Pending($chain)
+            }
+            Errored(err) => Errored(err),
+        }
+    }}
+}
+
+macro_rules! chain_n {
+    ($chain:ident, $fn_name:ident, $n:expr, $doc:expr; $($T:ident $idx:tt $t:ident),+) => {
+        #[doc = "Wraps "]
+        #[doc = $doc]
+        #[doc = " `AsyncEncode`s and encodes them in sequence."]
+        pub struct $chain<$($T),+> {
+            encs: ($(Option<$T>),+),
+            current: usize,
+        }
+
+        impl<$($T),+> $chain<$($T),+> {
+            #[doc = "Create a new `"]
+            #[doc = stringify!($chain)]
+            #[doc = "`, encoding the given encoders in order."]
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($($t: $T),+) -> $chain<$($T),+> {
+                $chain {
+                    encs: ($(Some($t)),+),
+                    current: 0,
+                }
+            }
+        }
+
+        impl<$($T),+> AsyncEncode for $chain<$($T),+>
+            where $($T: AsyncEncode),+
+        {
+            fn poll_encode<W: AsyncWrite>(mut self,
+                                          cx: &mut Context,
+                                          writer: &mut W)
+                                          -> PollEnc<Self> {
+                match self.current {
+                    $($idx => poll_at!(self, cx, writer, $idx, $n),)+
+                    _ => panic!("Polled chain after completion"),
+                }
+            }
+        }
+
+        impl<$($T),+> AsyncEncodeLen for $chain<$($T),+>
+            where $($T: AsyncEncodeLen),+
+        {
+            fn remaining_bytes(&self) -> usize {
+                0 $(+ self.encs.$idx.as_ref().map_or(0, |enc| enc.remaining_bytes()))+
+            }
+        }
+
+        #[doc = "Chain "]
+        #[doc = $doc]
+        #[doc = " encoders, encoding them in sequence."]
+        #[allow(clippy::too_many_arguments)]
+        pub fn $fn_name<$($T),+>($($t: $T),+) -> $chain<$($T),+> {
+            $chain::new($($t),+)
+        }
+    }
+}
+
+chain_n!(Chain3, chain3, 3, "three"; A 0 a, B 1 b, C 2 c);
+chain_n!(Chain4, chain4, 4, "four"; A 0 a, B 1 b, C 2 c, D 3 d);
+chain_n!(Chain5, chain5, 5, "five"; A 0 a, B 1 b, C 2 c, D 3 d, E 4 e);
+chain_n!(Chain6, chain6, 6, "six"; A 0 a, B 1 b, C 2 c, D 3 d, E 4 e, F 5 f);
+chain_n!(Chain7, chain7, 7, "seven"; A 0 a, B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g);
+chain_n!(Chain8, chain8, 8, "eight"; A 0 a, B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h);
+chain_n!(Chain9, chain9, 9, "nine";
+         A 0 a, B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i);
+chain_n!(Chain10, chain10, 10, "ten";
+         A 0 a, B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i, J 9 j);
+chain_n!(Chain11, chain11, 11, "eleven";
+         A 0 a, B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i, J 9 j, K 10 k);
+chain_n!(Chain12, chain12, 12, "twelve";
+         A 0 a, B 1 b, C 2 c, D 3 d, E 4 e, F 5 f, G 6 g, H 7 h, I 8 i, J 9 j, K 10 k, L 11 l);
+
+#[cfg(test)]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+
+    use async_byteorder::{decode_i32_native, decode_u64_native, decode_u8, encode_i32_native,
+                          encode_u64_native, encode_u8};
+    use super::super::super::testing::test_codec_len;
+    use super::super::super::decoder::chain5 as dec_chain5;
+    use super::super::super::encoder::chain5 as enc_chain5;
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, int_0: i32, int_1: u64, int_2: u8) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let dec = dec_chain5(decode_i32_native(), decode_u64_native(), decode_u8(), decode_u8(), decode_i32_native());
+            let enc = enc_chain5(encode_i32_native(int_0), encode_u64_native(int_1), encode_u8(int_2), encode_u8(int_2), encode_i32_native(int_0));
+            let test_outcome = test_codec_len(r, w, dec, enc);
+            test_outcome.1 && test_outcome.0 == (int_0, int_1, int_2, int_2, int_0)
+        }
+    }
+}
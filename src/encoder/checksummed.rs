@@ -0,0 +1,147 @@
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::{Async, Poll};
+use futures_core::task::Context;
+use futures_io::{AsyncWrite, Error as FutIoErr, ErrorKind};
+
+use checksum::Checksum;
+
+// Feeds all bytes written to the wrapped writer into a checksum.
+struct ChecksumWriter<'a, W: 'a, H: 'a> {
+    writer: &'a mut W,
+    checksum: &'a mut H,
+}
+
+impl<'a, W: AsyncWrite, H: Checksum> AsyncWrite for ChecksumWriter<'a, W, H> {
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<usize, FutIoErr> {
+        let res = self.writer.poll_write(cx, buf);
+        if let Ok(Async::Ready(written)) = res {
+            self.checksum.update(&buf[..written]);
+        }
+        res
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.writer.poll_flush(cx)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.writer.poll_close(cx)
+    }
+}
+
+enum State<C, H> {
+    Inner(C, H),
+    // The digest and how much of it has been written.
+    Trailer(Vec<u8>, usize),
+}
+
+/// Wraps an encoder, computing a checksum over all bytes it writes, and then appends the digest.
+pub struct Checksummed<C, H>(State<C, H>);
+
+impl<C, H> Checksummed<C, H> {
+    /// Create a new `Checksummed`, delegating to the given `enc` and appending a digest of the given
+    /// `checksum` over the data it wrote.
+    pub fn new(enc: C, checksum: H) -> Checksummed<C, H> {
+        Checksummed(State::Inner(enc, checksum))
+    }
+}
+
+impl<C, H> AsyncEncode for Checksummed<C, H>
+    where C: AsyncEncode,
+          H: Checksum
+{
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        match self.0 {
+            State::Inner(enc, mut checksum) => {
+                let res = enc.poll_encode(cx,
+                                          &mut ChecksumWriter {
+                                                   writer,
+                                                   checksum: &mut checksum,
+                                               });
+
+                match res {
+                    Done(written) => {
+                        self.0 = State::Trailer(checksum.digest(), 0);
+                        Progress(self, written)
+                    }
+                    Progress(enc, written) => {
+                        self.0 = State::Inner(enc, checksum);
+                        Progress(self, written)
+                    }
+                    Pending(enc) => {
+                        self.0 = State::Inner(enc, checksum);
+                        Pending(self)
+                    }
+                    Errored(err) => Errored(err),
+                }
+            }
+
+            State::Trailer(digest, offset) => {
+                match writer.poll_write(cx, &digest[offset..]) {
+                    Ok(Async::Ready(0)) => {
+                        Errored(FutIoErr::new(ErrorKind::WriteZero, "checksummed"))
+                    }
+                    Ok(Async::Ready(written)) => {
+                        if offset + written < digest.len() {
+                            self.0 = State::Trailer(digest, offset + written);
+                            Progress(self, written)
+                        } else {
+                            Done(written)
+                        }
+                    }
+                    Ok(Async::Pending) => {
+                        self.0 = State::Trailer(digest, offset);
+                        Pending(self)
+                    }
+                    Err(err) => Errored(err),
+                }
+            }
+        }
+    }
+}
+
+impl<C, H> AsyncEncodeLen for Checksummed<C, H>
+    where C: AsyncEncodeLen,
+          H: Checksum
+{
+    fn remaining_bytes(&self) -> usize {
+        match self.0 {
+            State::Inner(ref enc, _) => enc.remaining_bytes() + H::LEN,
+            State::Trailer(ref digest, offset) => digest.len() - offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+
+    use async_byteorder::{decode_i32_native, decode_u64_native, encode_i32_native,
+                          encode_u64_native};
+    use super::super::super::testing::test_codec_len;
+    use super::super::super::checksum::{Adler32, Crc32, Crc32c};
+    use super::super::super::decoder::{self, chain as dec_chain};
+    use super::super::super::encoder::{self, chain as enc_chain};
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, int_0: i32, int_1: u64) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let dec = dec_chain(decoder::checksummed(decode_i32_native(), Crc32::new()),
+                                decoder::checksummed(decode_u64_native(), Crc32c::new()));
+            let dec = decoder::checksummed(dec, Adler32::new());
+            let enc = enc_chain(encoder::checksummed(encode_i32_native(int_0), Crc32::new()),
+                                encoder::checksummed(encode_u64_native(int_1), Crc32c::new()));
+            let enc = encoder::checksummed(enc, Adler32::new());
+
+            let test_outcome = test_codec_len(r, w, dec, enc);
+            test_outcome.1 && (test_outcome.0).0 == int_0 && (test_outcome.0).1 == int_1
+        }
+    }
+}
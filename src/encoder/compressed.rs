@@ -0,0 +1,171 @@
+use async_codec::{AsyncEncode, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::{Async, Poll};
+use futures_core::task::Context;
+use futures_io::{AsyncWrite, Error as FutIoErr, ErrorKind};
+
+use compression::Compressor;
+
+const CHUNK: usize = 8 * 1024;
+
+// Collects the output of the inner encoder in memory.
+struct VecWriter<'a>(&'a mut Vec<u8>);
+
+impl<'a> AsyncWrite for VecWriter<'a> {
+    fn poll_write(&mut self, _: &mut Context, buf: &[u8]) -> Poll<usize, FutIoErr> {
+        self.0.extend_from_slice(buf);
+        Ok(Async::Ready(buf.len()))
+    }
+
+    fn poll_flush(&mut self, _: &mut Context) -> Poll<(), FutIoErr> {
+        Ok(Async::Ready(()))
+    }
+
+    fn poll_close(&mut self, _: &mut Context) -> Poll<(), FutIoErr> {
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Wraps an encoder and compresses its output on the fly.
+///
+/// The compressed size is not known in advance, so this does not implement `AsyncEncodeLen`.
+pub struct Compressed<C, Z> {
+    // `None` once the inner encoder is done.
+    enc: Option<C>,
+    compressor: Z,
+    // Output of the inner encoder that has not been compressed yet.
+    input: Vec<u8>,
+    // Compressed data, of which `output[offset..len]` has not been written yet.
+    output: Vec<u8>,
+    offset: usize,
+    len: usize,
+    done: bool,
+}
+
+impl<C, Z> Compressed<C, Z> {
+    /// Create a new `Compressed`, compressing the output of `enc` with the given `compressor`.
+    pub fn new(enc: C, compressor: Z) -> Compressed<C, Z> {
+        Compressed {
+            enc: Some(enc),
+            compressor,
+            input: Vec::new(),
+            output: vec![0; CHUNK],
+            offset: 0,
+            len: 0,
+            done: false,
+        }
+    }
+}
+
+impl<C, Z> AsyncEncode for Compressed<C, Z>
+    where C: AsyncEncode,
+          Z: Compressor
+{
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        // Compress until there is something to write.
+        while self.offset == self.len {
+            if self.done {
+                return Done(0);
+            }
+
+            if self.input.is_empty() {
+                if let Some(enc) = self.enc.take() {
+                    match enc.poll_encode(cx, &mut VecWriter(&mut self.input)) {
+                        Done(_) => {}
+                        Progress(enc, _) => self.enc = Some(enc),
+                        Pending(enc) => {
+                            self.enc = Some(enc);
+                            return Pending(self);
+                        }
+                        Errored(err) => return Errored(err),
+                    }
+                }
+            }
+
+            let finish = self.enc.is_none();
+            let step = match self.compressor.compress(&self.input, &mut self.output, finish) {
+                Ok(step) => step,
+                Err(err) => return Errored(err),
+            };
+            self.input.drain(..step.consumed);
+            self.offset = 0;
+            self.len = step.produced;
+            self.done = step.done;
+        }
+
+        match writer.poll_write(cx, &self.output[self.offset..self.len]) {
+            Ok(Async::Ready(0)) => Errored(FutIoErr::new(ErrorKind::WriteZero, "compressed")),
+            Ok(Async::Ready(written)) => {
+                self.offset += written;
+                if self.done && self.offset == self.len {
+                    Done(written)
+                } else {
+                    Progress(self, written)
+                }
+            }
+            Ok(Async::Pending) => Pending(self),
+            Err(err) => Errored(err),
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "flate2", feature = "zstd")))]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use quickcheck::TestResult;
+
+    use super::super::super::testing::test_codec;
+    use super::super::super::decoder::{self, until_delimiter as dec_until_delimiter};
+    use super::super::super::encoder::{self, until_delimiter as enc_until_delimiter};
+    #[cfg(feature = "flate2")]
+    use super::super::super::compression::{DeflateCompressor, DeflateDecompressor};
+    #[cfg(feature = "zstd")]
+    use super::super::super::compression::{ZstdCompressor, ZstdDecompressor};
+
+    #[cfg(feature = "flate2")]
+    quickcheck! {
+        fn deflate(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, data: Vec<u8>, zlib: bool) -> TestResult {
+            let enc = match enc_until_delimiter(data.clone(), b"\xff\x00") {
+                Ok(enc) => enc,
+                Err(_) => return TestResult::discard(),
+            };
+
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let enc = encoder::compressed(enc, DeflateCompressor::new(6, zlib));
+            let dec = decoder::decompressed(dec_until_delimiter(b"\xff\x00", data.len()),
+                                            DeflateDecompressor::new(zlib),
+                                            data.len() + 2);
+            let test_outcome = test_codec(r, w, dec, enc);
+            TestResult::from_bool(test_outcome.1 && test_outcome.0 == data)
+        }
+    }
+
+    #[cfg(feature = "zstd")]
+    quickcheck! {
+        fn zstd(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, data: Vec<u8>) -> TestResult {
+            let enc = match enc_until_delimiter(data.clone(), b"\xff\x00") {
+                Ok(enc) => enc,
+                Err(_) => return TestResult::discard(),
+            };
+
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let enc = encoder::compressed(enc, ZstdCompressor::new(0).unwrap());
+            let dec = decoder::decompressed(dec_until_delimiter(b"\xff\x00", data.len()),
+                                            ZstdDecompressor::new().unwrap(),
+                                            data.len() + 2);
+            let test_outcome = test_codec(r, w, dec, enc);
+            TestResult::from_bool(test_outcome.1 && test_outcome.0 == data)
+        }
+    }
+}
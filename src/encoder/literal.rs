@@ -0,0 +1,89 @@
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::Async::{Ready, Pending as AsyncPending};
+use futures_core::task::Context;
+use futures_io::{AsyncWrite, Error as FutIoErr, ErrorKind};
+
+/// Encodes exactly the given bytes, e.g. a magic number.
+pub struct Literal {
+    bytes: &'static [u8],
+    offset: usize,
+}
+
+impl Literal {
+    /// Create a new `Literal`, encoding the given `bytes`.
+    pub fn new(bytes: &'static [u8]) -> Literal {
+        Literal { bytes, offset: 0 }
+    }
+}
+
+impl AsyncEncode for Literal {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        if self.offset == self.bytes.len() {
+            return Done(0);
+        }
+
+        match writer.poll_write(cx, &self.bytes[self.offset..]) {
+            Ok(Ready(0)) => Errored(FutIoErr::new(ErrorKind::WriteZero, "literal")),
+            Ok(Ready(written)) => {
+                self.offset += written;
+                if self.offset == self.bytes.len() {
+                    Done(written)
+                } else {
+                    Progress(self, written)
+                }
+            }
+            Ok(AsyncPending) => Pending(self),
+            Err(err) => Errored(err),
+        }
+    }
+}
+
+impl AsyncEncodeLen for Literal {
+    fn remaining_bytes(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+}
+
+static ZEROS: [u8; 256] = [0; 256];
+
+/// Encodes a number of zero bytes, e.g. for reserved fields or padding.
+pub struct Zeros {
+    remaining: usize,
+}
+
+impl Zeros {
+    /// Create a new `Zeros`, encoding `n` zero bytes.
+    pub fn new(n: usize) -> Zeros {
+        Zeros { remaining: n }
+    }
+}
+
+impl AsyncEncode for Zeros {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        if self.remaining == 0 {
+            return Done(0);
+        }
+
+        let len = ZEROS.len().min(self.remaining);
+        match writer.poll_write(cx, &ZEROS[..len]) {
+            Ok(Ready(0)) => Errored(FutIoErr::new(ErrorKind::WriteZero, "zeros")),
+            Ok(Ready(written)) => {
+                self.remaining -= written;
+                if self.remaining == 0 {
+                    Done(written)
+                } else {
+                    Progress(self, written)
+                }
+            }
+            Ok(AsyncPending) => Pending(self),
+            Err(err) => Errored(err),
+        }
+    }
+}
+
+impl AsyncEncodeLen for Zeros {
+    fn remaining_bytes(&self) -> usize {
+        self.remaining
+    }
+}
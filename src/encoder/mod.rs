@@ -1,9 +1,90 @@
 //! Utilities for working with encoders.
 
+mod aligned;
+pub use self::aligned::Aligned;
+mod boxed;
+pub use self::boxed::{BoxEncoder, BoxEncoderLen, BoxedEncode};
 mod chain;
 pub use self::chain::Chain;
+mod chain_n;
+pub use self::chain_n::{Chain3, Chain4, Chain5, Chain6, Chain7, Chain8, Chain9, Chain10, Chain11, Chain12,
+                        chain3, chain4, chain5, chain6, chain7, chain8, chain9, chain10, chain11, chain12};
+mod checksummed;
+pub use self::checksummed::Checksummed;
+mod compressed;
+pub use self::compressed::Compressed;
+mod literal;
+pub use self::literal::{Literal, Zeros};
+mod tee;
+pub use self::tee::Tee;
+mod then;
+pub use self::then::Then;
+mod until_delimiter;
+pub use self::until_delimiter::{UntilDelimiter, ContainsDelimiter};
+mod recursive;
+pub use self::recursive::{Recursive, Recursion};
+#[cfg(feature = "serde")]
+mod serde_bincode;
+#[cfg(feature = "serde")]
+pub use self::serde_bincode::SerdeBincode;
 
 /// Chain two encoders, encoding them in sequence.
 pub fn chain<S, T>(first: S, second: T) -> Chain<S, T> {
     Chain::new(first, second)
 }
+
+/// Wrap an encoder, writing zero bytes after it up to the next multiple of `alignment`.
+pub fn aligned<C>(enc: C, alignment: usize) -> Aligned<C> {
+    Aligned::new(enc, alignment)
+}
+
+/// Encode exactly the given bytes.
+pub fn literal(bytes: &'static [u8]) -> Literal {
+    Literal::new(bytes)
+}
+
+/// Encode `n` zero bytes.
+pub fn zeros(n: usize) -> Zeros {
+    Zeros::new(n)
+}
+
+/// Run an encoder and then run the encoder constructed from the number of bytes it wrote.
+pub fn then<S, T, F>(first: S, f: F) -> Then<S, T, F> {
+    Then::new(first, f)
+}
+
+/// Encode the given bytes followed by `delim`, erroring if they contain the delimiter.
+pub fn until_delimiter<B: AsRef<[u8]>>(bytes: B,
+                                       delim: &[u8])
+                                       -> Result<UntilDelimiter<B>, ContainsDelimiter> {
+    UntilDelimiter::new(bytes, delim)
+}
+
+/// Wrap an encoder, appending a digest of the given checksum over the bytes it writes.
+pub fn checksummed<C, H>(enc: C, checksum: H) -> Checksummed<C, H> {
+    Checksummed::new(enc, checksum)
+}
+
+/// Wrap an encoder, feeding all bytes it writes into the given sink.
+pub fn tee<C, S>(enc: C, sink: S) -> Tee<C, S> {
+    Tee::new(enc, sink)
+}
+
+/// Wrap an encoder, compressing its output with the given compressor.
+pub fn compressed<C, Z>(enc: C, compressor: Z) -> Compressed<C, Z> {
+    Compressed::new(enc, compressor)
+}
+
+/// Create an encoder that can refer to itself, see `Recursive::new`.
+pub fn recursive<'a, T, C, F>(max_depth: usize, value: T, f: F) -> Recursive<C>
+    where F: Fn(Recursion<'a, T, C>, T) -> C + 'a
+{
+    Recursive::new(max_depth, value, f)
+}
+
+/// Create an encoder for a serde value, encoding it via bincode and prefixing it with its length.
+#[cfg(feature = "serde")]
+pub fn serde_bincode<T: ?Sized + ::serde::Serialize>(value: &T)
+                                                     -> Result<SerdeBincode, ::bincode::Error> {
+    SerdeBincode::new(value)
+}
@@ -0,0 +1,165 @@
+use std::rc::Rc;
+
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::{AsyncWrite, Error as FutIoErr, ErrorKind};
+
+use super::BoxEncoder;
+
+/// A handle through which a recursive encoder refers to itself.
+pub struct Recursion<'a, T, C = BoxEncoder<'a>> {
+    f: Rc<dyn Fn(Recursion<'a, T, C>, T) -> C + 'a>,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'a, T, C> Clone for Recursion<'a, T, C> {
+    fn clone(&self) -> Recursion<'a, T, C> {
+        Recursion {
+            f: self.f.clone(),
+            depth: self.depth,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+impl<'a, T, C> Recursion<'a, T, C> {
+    /// Create an encoder for a nested value.
+    ///
+    /// The nested encoder is constructed right away, so that its length is known. Construction
+    /// stops once the maximum depth is exceeded, the resulting encoder then errors when polled.
+    pub fn encoder(&self, value: T) -> Recursive<C> {
+        Recursion {
+                f: self.f.clone(),
+                depth: self.depth + 1,
+                max_depth: self.max_depth,
+            }
+            .build(value)
+    }
+
+    /// The nesting depth of the encoder this handle was passed to, starting at 0.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    fn build(self, value: T) -> Recursive<C> {
+        if self.depth > self.max_depth {
+            Recursive(None)
+        } else {
+            Recursive(Some((self.f.clone())(self, value)))
+        }
+    }
+}
+
+/// An encoder defined in terms of itself, created via `encoder::recursive`.
+///
+/// `C` is the type of the encoders the recursion produces, it has to box its nested encoders.
+/// This is an `AsyncEncodeLen` if `C` is, e.g. a `BoxEncoderLen`.
+pub struct Recursive<C>(Option<C>);
+
+impl<C> Recursive<C> {
+    /// Create a new `Recursive`, obtaining the actual encoder for `value` by calling `f` with it
+    /// and a handle to create encoders for nested values. Errors with `InvalidInput` once more
+    /// than `max_depth` encoders are nested within each other.
+    pub fn new<'a, T, F>(max_depth: usize, value: T, f: F) -> Recursive<C>
+        where F: Fn(Recursion<'a, T, C>, T) -> C + 'a
+    {
+        Recursion {
+                f: Rc::new(f),
+                depth: 0,
+                max_depth,
+            }
+            .build(value)
+    }
+}
+
+impl<C: AsyncEncode> AsyncEncode for Recursive<C> {
+    fn poll_encode<W: AsyncWrite>(self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        let enc = match self.0 {
+            Some(enc) => enc,
+            None => {
+                return Errored(FutIoErr::new(ErrorKind::InvalidInput,
+                                             "nesting exceeds maximum depth"))
+            }
+        };
+
+        match enc.poll_encode(cx, writer) {
+            Done(written) => Done(written),
+            Progress(enc, written) => Progress(Recursive(Some(enc)), written),
+            Pending(enc) => Pending(Recursive(Some(enc))),
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+impl<C: AsyncEncodeLen> AsyncEncodeLen for Recursive<C> {
+    fn remaining_bytes(&self) -> usize {
+        self.0.as_ref().map_or(0, |enc| enc.remaining_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use async_codec::AsyncEncodeLen;
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use futures_executor::block_on;
+    use futures_io::ErrorKind;
+
+    use super::super::super::encode;
+    use super::super::super::testing::{test_codec_len, GreedyDecoder};
+    use super::super::{self as encoder, BoxEncoderLen, BoxedEncode, Recursion, Recursive};
+    use async_byteorder::encode_u8;
+
+    struct Tree(u8, Vec<Tree>);
+
+    // A tree is a value byte, the number of children and then the children.
+    fn tree_encoder(max_depth: usize, tree: Tree) -> Recursive<BoxEncoderLen<'static>> {
+        encoder::recursive(max_depth,
+                           tree,
+                           |rec: Recursion<Tree, BoxEncoderLen>, Tree(value, kids)| {
+            let count = kids.len() as u8;
+            let head = encoder::chain(encode_u8(value), encode_u8(count)).boxed_len();
+            kids.into_iter()
+                .fold(head, |enc, kid| encoder::chain(enc, rec.encoder(kid)).boxed_len())
+        })
+    }
+
+    fn sample() -> Tree {
+        Tree(1,
+             vec![Tree(2, vec![]),
+                  Tree(3, vec![Tree(4, vec![Tree(5, vec![])]), Tree(6, vec![])])])
+    }
+
+    const ENCODED: [u8; 12] = [1, 2, 2, 0, 3, 2, 4, 1, 5, 0, 6, 0];
+
+    #[test]
+    fn too_deep() {
+        let enc = tree_encoder(3, sample());
+        assert_eq!(enc.remaining_bytes(), ENCODED.len());
+        let (writer, written) = block_on(encode(Cursor::new(Vec::new()), enc)).unwrap();
+        assert_eq!((&writer.into_inner()[..], written), (&ENCODED[..], ENCODED.len()));
+
+        match block_on(encode(Cursor::new(Vec::new()), tree_encoder(2, sample()))) {
+            Err((_, err)) => assert_eq!(err.kind(), ErrorKind::InvalidInput),
+            Ok(_) => panic!(),
+        }
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let dec = GreedyDecoder::new(ENCODED.len());
+            let test_outcome = test_codec_len(r, w, dec, tree_encoder(3, sample()));
+            test_outcome.1 && test_outcome.0 == ENCODED
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use bincode;
+use futures_core::Async;
+use futures_core::task::Context;
+use futures_io::{AsyncWrite, Error as FutIoErr, ErrorKind};
+use serde::Serialize;
+
+use field::FixedEncoder;
+
+/// Encodes a serde value via bincode, prefixed by the length of the encoding as a varint.
+///
+/// The value is serialized when creating the encoder, so its length is known up front.
+pub struct SerdeBincode {
+    // `None` once the length prefix has been written.
+    prefix: Option<FixedEncoder>,
+    bytes: Vec<u8>,
+    offset: usize,
+}
+
+impl SerdeBincode {
+    /// Create a new `SerdeBincode`, erroring if bincode fails to serialize the value.
+    pub fn new<T: Serialize + ?Sized>(value: &T) -> Result<SerdeBincode, bincode::Error> {
+        let bytes = bincode::serialize(value)?;
+        Ok(SerdeBincode {
+               prefix: Some(FixedEncoder::varint(bytes.len() as u64)),
+               bytes,
+               offset: 0,
+           })
+    }
+}
+
+impl AsyncEncode for SerdeBincode {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        if let Some(prefix) = self.prefix.take() {
+            return match prefix.poll_encode(cx, writer) {
+                       Done(written) => {
+                           if self.bytes.is_empty() {
+                               Done(written)
+                           } else {
+                               Progress(self, written)
+                           }
+                       }
+                       Progress(prefix, written) => {
+                           self.prefix = Some(prefix);
+                           Progress(self, written)
+                       }
+                       Pending(prefix) => {
+                           self.prefix = Some(prefix);
+                           Pending(self)
+                       }
+                       Errored(err) => Errored(err),
+                   };
+        }
+
+        match writer.poll_write(cx, &self.bytes[self.offset..]) {
+            Ok(Async::Ready(0)) => Errored(FutIoErr::new(ErrorKind::WriteZero, "serde bincode")),
+            Ok(Async::Ready(written)) => {
+                self.offset += written;
+                if self.offset == self.bytes.len() {
+                    Done(written)
+                } else {
+                    Progress(self, written)
+                }
+            }
+            Ok(Async::Pending) => Pending(self),
+            Err(err) => Errored(err),
+        }
+    }
+}
+
+impl AsyncEncodeLen for SerdeBincode {
+    fn remaining_bytes(&self) -> usize {
+        self.prefix.as_ref().map_or(0, |prefix| prefix.remaining_bytes()) + self.bytes.len() -
+        self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_codec::DecodeError;
+    use async_ringbuffer::ring_buffer;
+    use bincode;
+    use futures_executor::block_on;
+
+    use super::super::super::testing::test_codec_len;
+    use super::super::super::{decode, decoder};
+    use super::super::super::decoder::SerdeBincodeError;
+    use super::super::super::encoder;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Message {
+        id: u64,
+        name: String,
+        payload: Vec<u8>,
+        reply_to: Option<i32>,
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, id: u64, name: String, payload: Vec<u8>, reply_to: Option<i32>) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let msg = Message { id, name, payload, reply_to };
+            let enc = encoder::chain(encoder::serde_bincode(&msg).unwrap(),
+                                     encoder::serde_bincode(&42u8).unwrap());
+            let dec = decoder::chain(decoder::serde_bincode::<Message>(),
+                                     decoder::serde_bincode::<u8>());
+            let test_outcome = test_codec_len(r, w, dec, enc);
+            test_outcome.1 && test_outcome.0 == (msg, 42)
+        }
+    }
+
+    #[test]
+    fn trailing_bytes() {
+        let mut frame = bincode::serialize(&42u8).unwrap();
+        frame.push(7);
+        frame.insert(0, frame.len() as u8);
+
+        match block_on(decode(&frame[..], decoder::serde_bincode::<u8>())) {
+            Err((_, DecodeError::DataError(SerdeBincodeError::Bincode(_)))) => {}
+            _ => panic!(),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::{Async, Poll};
+use futures_core::task::Context;
+use futures_io::{AsyncWrite, Error as FutIoErr};
+
+use sink::ByteSink;
+
+// Feeds all bytes written to the wrapped writer into a sink.
+struct TeeWriter<'a, W: 'a, S: 'a> {
+    writer: &'a mut W,
+    sink: &'a mut S,
+}
+
+impl<'a, W: AsyncWrite, S: ByteSink> AsyncWrite for TeeWriter<'a, W, S> {
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<usize, FutIoErr> {
+        let res = self.writer.poll_write(cx, buf);
+        if let Ok(Async::Ready(written)) = res {
+            self.sink.consume(&buf[..written]);
+        }
+        res
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.writer.poll_flush(cx)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.writer.poll_close(cx)
+    }
+}
+
+/// Wraps an encoder and feeds all bytes it writes into a `ByteSink`.
+///
+/// The sink is dropped once encoding is done, so use a `&mut` reference to a sink (or a
+/// `sink::Callback`) to access the written bytes.
+pub struct Tee<C, S> {
+    enc: C,
+    sink: S,
+}
+
+impl<C, S> Tee<C, S> {
+    /// Create a new `Tee`, delegating to the given `enc` and feeding the bytes it writes into the
+    /// given `sink`.
+    pub fn new(enc: C, sink: S) -> Tee<C, S> {
+        Tee { enc, sink }
+    }
+}
+
+impl<C, S> AsyncEncode for Tee<C, S>
+    where C: AsyncEncode,
+          S: ByteSink
+{
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        let res = self.enc
+            .poll_encode(cx,
+                         &mut TeeWriter {
+                                  writer,
+                                  sink: &mut self.sink,
+                              });
+
+        match res {
+            Done(written) => Done(written),
+            Progress(enc, written) => {
+                self.enc = enc;
+                Progress(self, written)
+            }
+            Pending(enc) => {
+                self.enc = enc;
+                Pending(self)
+            }
+            Errored(err) => Errored(err),
+        }
+    }
+}
+
+impl<C, S> AsyncEncodeLen for Tee<C, S>
+    where C: AsyncEncodeLen,
+          S: ByteSink
+{
+    fn remaining_bytes(&self) -> usize {
+        self.enc.remaining_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+
+    use async_byteorder::{decode_i32_be, decode_u64_be, encode_i32_be, encode_u64_be};
+    use super::super::super::testing::test_codec_len;
+    use super::super::super::decoder::{self, chain as dec_chain};
+    use super::super::super::encoder::{self, chain as enc_chain};
+    use super::super::super::sink::callback;
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, int_0: i32, int_1: u64) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let mut written = Vec::new();
+            let mut called = 0;
+            let test_outcome = {
+                let dec = decoder::tee(dec_chain(decode_i32_be(), decode_u64_be()), Vec::new());
+                let enc = enc_chain(encoder::tee(encode_i32_be(int_0), &mut written),
+                                    encoder::tee(encode_u64_be(int_1), callback(|bytes: &[u8]| called += bytes.len())));
+                test_codec_len(r, w, dec, enc)
+            };
+            let ((item, read), ok) = test_outcome;
+
+            let expected = [(int_0 >> 24) as u8, (int_0 >> 16) as u8, (int_0 >> 8) as u8, int_0 as u8];
+            ok && item == (int_0, int_1) && written == expected && called == 8 &&
+            read[..4] == expected && read.len() == 12
+        }
+    }
+}
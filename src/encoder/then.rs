@@ -0,0 +1,92 @@
+use async_codec::{AsyncEncode, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::task::Context;
+use futures_io::AsyncWrite;
+
+enum State<S, T, F> {
+    // The first encoder, the function to create the second one and the bytes written so far.
+    First(S, F, usize),
+    Second(T),
+}
+
+/// Run an encoder and then run the encoder constructed from the number of bytes it wrote.
+///
+/// The second encoder is only constructed once the first one is done, so it can encode e.g. a
+/// trailer depending on the length of the preceding data.
+pub struct Then<S, T, F>(State<S, T, F>);
+
+impl<S, T, F> Then<S, T, F> {
+    /// Run an encoder and then run the encoder constructed from the number of bytes it wrote.
+    pub fn new(first: S, f: F) -> Then<S, T, F> {
+        Then(State::First(first, f, 0))
+    }
+}
+
+impl<S, T, F> AsyncEncode for Then<S, T, F>
+    where S: AsyncEncode,
+          T: AsyncEncode,
+          F: FnOnce(usize) -> T
+{
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        match self.0 {
+            State::First(first, f, total) => {
+                match first.poll_encode(cx, writer) {
+                    Done(written) => {
+                        self.0 = State::Second(f(total + written));
+                        Progress(self, written)
+                    }
+                    Progress(first, written) => {
+                        self.0 = State::First(first, f, total + written);
+                        Progress(self, written)
+                    }
+                    Pending(first) => {
+                        self.0 = State::First(first, f, total);
+                        Pending(self)
+                    }
+                    Errored(err) => Errored(err),
+                }
+            }
+            State::Second(second) => {
+                match second.poll_encode(cx, writer) {
+                    Done(written) => Done(written),
+                    Progress(second, written) => {
+                        self.0 = State::Second(second);
+                        Progress(self, written)
+                    }
+                    Pending(second) => {
+                        self.0 = State::Second(second);
+                        Pending(self)
+                    }
+                    Errored(err) => Errored(err),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use async_byteorder::{decode_u8, decode_u64_be, encode_u8, encode_u64_be};
+
+    use super::*;
+    use super::super::super::testing::test_codec;
+    use super::super::super::decoder;
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, value: u64) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            // Append the length of the preceding data as a trailer.
+            let enc = Then::new(encode_u64_be(value), |written| encode_u8(written as u8));
+            let dec = decoder::chain(decode_u64_be(), decode_u8());
+            let test_outcome = test_codec(r, w, dec, enc);
+            test_outcome.1 && test_outcome.0 == (value, 8)
+        }
+    }
+}
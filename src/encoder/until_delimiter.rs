@@ -0,0 +1,127 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncEncode, AsyncEncodeLen, PollEnc};
+use async_codec::PollEnc::{Done, Progress, Pending, Errored};
+use futures_core::Async::{Ready, Pending as AsyncPending};
+use futures_core::task::Context;
+use futures_io::{AsyncWrite, Error as FutIoErr, ErrorKind};
+
+/// The data given to an `UntilDelimiter` would be misread by a decoder, because a delimiter would
+/// be found before the one appended by the encoder. Contains the offset of that delimiter.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ContainsDelimiter(pub usize);
+
+impl Display for ContainsDelimiter {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "Data contains the delimiter at offset {}", self.0)
+    }
+}
+
+impl Error for ContainsDelimiter {}
+
+/// Encodes some bytes followed by a delimiter.
+pub struct UntilDelimiter<B> {
+    bytes: B,
+    delim: Vec<u8>,
+    offset: usize,
+}
+
+impl<B: AsRef<[u8]>> UntilDelimiter<B> {
+    /// Create a new `UntilDelimiter`, encoding the given `bytes` followed by `delim`.
+    ///
+    /// Errors if decoding the output with a `decoder::UntilDelimiter` would not yield the given
+    /// `bytes`, i.e. if the delimiter occurs in them (possibly overlapping the appended delimiter).
+    ///
+    /// Panics if `delim` is empty.
+    pub fn new(bytes: B, delim: &[u8]) -> Result<UntilDelimiter<B>, ContainsDelimiter> {
+        assert!(!delim.is_empty(), "Delimiter must not be empty");
+
+        let mut data = bytes.as_ref().to_vec();
+        data.extend_from_slice(&delim[..delim.len() - 1]);
+        match data.windows(delim.len()).position(|window| window == delim) {
+            Some(offset) => Err(ContainsDelimiter(offset)),
+            None => {
+                Ok(UntilDelimiter {
+                       bytes,
+                       delim: delim.to_vec(),
+                       offset: 0,
+                   })
+            }
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> AsyncEncode for UntilDelimiter<B> {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        let res = {
+            let bytes = self.bytes.as_ref();
+            if self.offset < bytes.len() {
+                writer.poll_write(cx, &bytes[self.offset..])
+            } else {
+                writer.poll_write(cx, &self.delim[self.offset - bytes.len()..])
+            }
+        };
+
+        match res {
+            Ok(Ready(0)) => Errored(FutIoErr::new(ErrorKind::WriteZero, "until_delimiter")),
+            Ok(Ready(written)) => {
+                self.offset += written;
+                if self.remaining_bytes() == 0 {
+                    Done(written)
+                } else {
+                    Progress(self, written)
+                }
+            }
+            Ok(AsyncPending) => Pending(self),
+            Err(err) => Errored(err),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> AsyncEncodeLen for UntilDelimiter<B> {
+    fn remaining_bytes(&self) -> usize {
+        self.bytes.as_ref().len() + self.delim.len() - self.offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use quickcheck::TestResult;
+
+    use super::*;
+    use super::super::super::testing::test_codec_len;
+    use super::super::super::decoder::until_delimiter as dec_until_delimiter;
+
+    #[test]
+    fn contains_delimiter() {
+        assert_eq!(UntilDelimiter::new(b"xaay", b"aa").err(),
+                   Some(ContainsDelimiter(1)));
+        assert_eq!(UntilDelimiter::new(b"ab", b"aba").err(),
+                   Some(ContainsDelimiter(0)));
+        assert!(UntilDelimiter::new(b"ba", b"aba").is_ok());
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, data: Vec<u8>, delim: Vec<u8>) -> TestResult {
+            if delim.is_empty() {
+                return TestResult::discard();
+            }
+            let enc = match UntilDelimiter::new(data.clone(), &delim) {
+                Ok(enc) => enc,
+                Err(_) => return TestResult::discard(),
+            };
+
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let test_outcome = test_codec_len(r, w, dec_until_delimiter(&delim, data.len()), enc);
+            TestResult::from_bool(test_outcome.1 && test_outcome.0 == data)
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use futures_core::Never;
+use futures_io::Error as FutIoErr;
+#[cfg(feature = "serde")]
+use bincode;
+
+use decoder::{AlignedError, ChecksumError, ContextError, DecodeExactError, DecompressError,
+              DepthExceeded, LiteralMismatch, OrError, UntilDelimiterError};
+use field::FieldError;
+use netstring::NetstringError;
+#[cfg(feature = "box-stream")]
+use box_stream::BoxStreamError;
+#[cfg(feature = "serde")]
+use decoder::SerdeBincodeError;
+
+/// A data error that all errors of this crate convert into.
+///
+/// Using this as the error type of composed decoders avoids nesting wrapper errors: `Chain`,
+/// `AndThen` and the `chain3` to `chain12` decoders convert the errors of their parts via `Into`,
+/// so parts with different error types can be composed into a decoder erroring with a
+/// `CodecError`. Wrapper errors are flattened by converting their inner errors as well.
+///
+/// Which variants exist depends on the enabled features, and new ones may be added, so matches on
+/// it need a wildcard arm.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CodecError {
+    /// A field held an invalid value.
+    Field(FieldError),
+    /// The data did not match an expected literal.
+    Literal(LiteralMismatch),
+    /// No delimiter was found within the contained maximum number of bytes.
+    DelimiterTooLong(usize),
+    /// Nesting exceeded the contained maximum depth.
+    DepthExceeded(usize),
+    /// A decoder finished early, after the contained number of bytes.
+    Early(usize),
+    /// A checksum did not match the checksummed data.
+    ChecksumMismatch,
+    /// Padding to an alignment contained a nonzero byte at the contained offset.
+    NonZeroPadding(usize),
+    /// An `Or` exceeded the contained backtrack buffer size.
+    BacktrackExceeded(usize),
+    /// Both alternatives of an `Or` failed.
+    Both(Box<CodecError>, Box<CodecError>),
+    /// The second alternative of an `Or` left the contained number of backtracked bytes unconsumed.
+    Leftover(usize),
+    /// Compressed data was invalid.
+    Corrupt(FutIoErr),
+    /// Some data exceeded the contained maximum length.
+    TooLarge(usize),
+    /// A decoder finished before all data of a compressed or encrypted stream was consumed.
+    TrailingData,
+    /// A length prefix was malformed or out of range.
+    InvalidLength,
+    /// A delimiter was expected, but the contained byte was read.
+    UnexpectedByte(u8),
+    /// Encrypted data failed authentication.
+    Unauthenticated,
+    /// Bincode failed to deserialize a value.
+    #[cfg(feature = "serde")]
+    Bincode(bincode::Error),
+    /// An error annotated with a field path and byte offset.
+    Context(ContextError),
+    /// Any other error, e.g. of a custom decoder. All errors of this crate convert into one of the
+    /// other variants.
+    Other(Box<dyn Error + Send + Sync>),
+}
+
+impl CodecError {
+    /// Create a `CodecError::Other` from an arbitrary error.
+    pub fn other<E: Into<Box<dyn Error + Send + Sync>>>(err: E) -> CodecError {
+        CodecError::Other(err.into())
+    }
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            CodecError::Field(ref err) => write!(f, "{}", err),
+            CodecError::Literal(ref err) => write!(f, "{}", err),
+            CodecError::DelimiterTooLong(max_len) => {
+                write!(f, "{}", UntilDelimiterError::TooLong(max_len))
+            }
+            CodecError::DepthExceeded(max_depth) => write!(f, "{}", DepthExceeded(max_depth)),
+            CodecError::Early(read) => {
+                write!(f, "Decoder finished early after reading {} bytes", read)
+            }
+            CodecError::ChecksumMismatch => write!(f, "Checksum mismatch"),
+            CodecError::NonZeroPadding(offset) => {
+                write!(f, "Nonzero padding byte at offset {}", offset)
+            }
+            CodecError::BacktrackExceeded(max) => {
+                write!(f, "Exceeded maximum backtrack buffer of {} bytes", max)
+            }
+            CodecError::Both(ref a, ref b) => {
+                write!(f, "Both alternatives failed, first: {}, second: {}", a, b)
+            }
+            CodecError::Leftover(len) => {
+                write!(f, "Second alternative left {} backtracked bytes unconsumed", len)
+            }
+            CodecError::Corrupt(ref err) => write!(f, "Corrupt compressed data: {}", err),
+            CodecError::TooLarge(max_len) => {
+                write!(f, "Data exceeds maximum of {} bytes", max_len)
+            }
+            CodecError::TrailingData => write!(f, "Trailing data after decoded item"),
+            CodecError::InvalidLength => write!(f, "Invalid length prefix"),
+            CodecError::UnexpectedByte(byte) => {
+                write!(f, "Expected a delimiter, got {:#04x}", byte)
+            }
+            CodecError::Unauthenticated => write!(f, "Data failed authentication"),
+            #[cfg(feature = "serde")]
+            CodecError::Bincode(ref err) => write!(f, "Bincode error: {}", err),
+            CodecError::Context(ref err) => write!(f, "{}", err),
+            CodecError::Other(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for CodecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            CodecError::Both(ref a, _) => Some(&**a),
+            CodecError::Corrupt(ref err) => Some(err),
+            #[cfg(feature = "serde")]
+            CodecError::Bincode(ref err) => Some(err),
+            CodecError::Context(ref err) => err.source(),
+            CodecError::Other(ref err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<Never> for CodecError {
+    fn from(never: Never) -> CodecError {
+        match never {}
+    }
+}
+
+impl From<FieldError> for CodecError {
+    fn from(err: FieldError) -> CodecError {
+        CodecError::Field(err)
+    }
+}
+
+impl From<LiteralMismatch> for CodecError {
+    fn from(err: LiteralMismatch) -> CodecError {
+        CodecError::Literal(err)
+    }
+}
+
+impl From<UntilDelimiterError> for CodecError {
+    fn from(err: UntilDelimiterError) -> CodecError {
+        match err {
+            UntilDelimiterError::TooLong(max_len) => CodecError::DelimiterTooLong(max_len),
+        }
+    }
+}
+
+impl From<DepthExceeded> for CodecError {
+    fn from(err: DepthExceeded) -> CodecError {
+        CodecError::DepthExceeded(err.0)
+    }
+}
+
+impl From<ContextError> for CodecError {
+    fn from(err: ContextError) -> CodecError {
+        CodecError::Context(err)
+    }
+}
+
+impl<E: Into<CodecError>, I> From<DecodeExactError<E, I>> for CodecError {
+    fn from(err: DecodeExactError<E, I>) -> CodecError {
+        match err {
+            DecodeExactError::Early(_, read) => CodecError::Early(read),
+            DecodeExactError::Inner(err) => err.into(),
+        }
+    }
+}
+
+impl<E: Into<CodecError>> From<ChecksumError<E>> for CodecError {
+    fn from(err: ChecksumError<E>) -> CodecError {
+        match err {
+            ChecksumError::Mismatch => CodecError::ChecksumMismatch,
+            ChecksumError::Inner(err) => err.into(),
+        }
+    }
+}
+
+impl<E: Into<CodecError>> From<AlignedError<E>> for CodecError {
+    fn from(err: AlignedError<E>) -> CodecError {
+        match err {
+            AlignedError::NonZeroPadding(offset) => CodecError::NonZeroPadding(offset),
+            AlignedError::Inner(err) => err.into(),
+        }
+    }
+}
+
+impl<A: Into<CodecError>, B: Into<CodecError>> From<OrError<A, B>> for CodecError {
+    fn from(err: OrError<A, B>) -> CodecError {
+        match err {
+            OrError::BacktrackExceeded(max) => CodecError::BacktrackExceeded(max),
+            OrError::Both(a, b) => CodecError::Both(Box::new(a.into()), Box::new(b.into())),
+            OrError::Leftover(len) => CodecError::Leftover(len),
+        }
+    }
+}
+
+impl<E: Into<CodecError>> From<DecompressError<E>> for CodecError {
+    fn from(err: DecompressError<E>) -> CodecError {
+        match err {
+            DecompressError::Corrupt(err) => CodecError::Corrupt(err),
+            DecompressError::TooLarge(max_len) => CodecError::TooLarge(max_len),
+            DecompressError::TrailingData => CodecError::TrailingData,
+            DecompressError::Inner(err) => err.into(),
+        }
+    }
+}
+
+impl<E: Into<CodecError>, I> From<NetstringError<E, I>> for CodecError {
+    fn from(err: NetstringError<E, I>) -> CodecError {
+        match err {
+            NetstringError::InvalidLength(_) |
+            NetstringError::LeadingZero |
+            NetstringError::Overflow => CodecError::InvalidLength,
+            NetstringError::TooLong(max_len) => CodecError::TooLarge(max_len),
+            NetstringError::MissingComma(byte) => CodecError::UnexpectedByte(byte),
+            NetstringError::Data(err) => err.into(),
+        }
+    }
+}
+
+#[cfg(feature = "box-stream")]
+impl<E: Into<CodecError>> From<BoxStreamError<E>> for CodecError {
+    fn from(err: BoxStreamError<E>) -> CodecError {
+        match err {
+            BoxStreamError::Unauthenticated => CodecError::Unauthenticated,
+            BoxStreamError::InvalidLength(_) => CodecError::InvalidLength,
+            BoxStreamError::TrailingData => CodecError::TrailingData,
+            BoxStreamError::Inner(err) => err.into(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SerdeBincodeError> for CodecError {
+    fn from(err: SerdeBincodeError) -> CodecError {
+        match err {
+            SerdeBincodeError::Length(err) => CodecError::Field(err),
+            SerdeBincodeError::TooLong(max_len) => CodecError::TooLarge(max_len),
+            SerdeBincodeError::Bincode(err) => CodecError::Bincode(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_byteorder::decode_u8;
+    use async_codec::DecodeError;
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::decode;
+    use super::super::decoder::{self, Context, DecodeExact};
+    use super::super::field;
+    use super::super::netstring;
+
+    #[test]
+    fn mixed_errors() {
+        let dec = || {
+            Context::new("msg",
+                         decoder::chain3(decode_u8(),
+                                         decoder::literal(b"ok"),
+                                         Context::new("flag", field::decoder::<bool>()))
+                                 .with_error::<CodecError>())
+        };
+
+        let data: &[u8] = &[1, b'o', b'k', 1];
+        assert_eq!(block_on(decode(data, dec())).unwrap().1, (1, (), true));
+
+        let data: &[u8] = &[1, b'o', b'x', 1];
+        match block_on(decode(data, dec())) {
+            Err((_, DecodeError::DataError(err))) => {
+                assert_eq!(err.path(), &["msg"]);
+                match err.inner().downcast_ref::<CodecError>() {
+                    Some(CodecError::Literal(err)) => assert_eq!(err.offset, 1),
+                    _ => panic!("expected LiteralMismatch"),
+                }
+            }
+            _ => panic!("expected DataError"),
+        }
+
+        let data: &[u8] = &[1, b'o', b'k', 7];
+        match block_on(decode(data, dec())) {
+            Err((_, DecodeError::DataError(err))) => {
+                assert_eq!(err.to_string(), "at byte 3, in msg.flag: Invalid bool 0x07");
+            }
+            _ => panic!("expected DataError"),
+        }
+    }
+
+    #[test]
+    fn flattening() {
+        let dec = decoder::err_into::<CodecError, _>(DecodeExact::new(field::decoder::<bool>(), 2));
+        let data: &[u8] = &[1, 1];
+        match block_on(decode(data, dec)) {
+            Err((_, DecodeError::DataError(CodecError::Early(1)))) => {}
+            _ => panic!("expected Early"),
+        }
+
+        let dec = decoder::err_into::<CodecError, _>(DecodeExact::new(field::decoder::<bool>(), 1));
+        let data: &[u8] = &[2];
+        match block_on(decode(data, dec)) {
+            Err((_, DecodeError::DataError(CodecError::Field(FieldError::InvalidBool(2))))) => {}
+            _ => panic!("expected InvalidBool"),
+        }
+    }
+
+    #[test]
+    fn netstring_variants() {
+        let dec = || {
+            decoder::err_into::<CodecError, _>(netstring::decode(decoder::skip(3), 8))
+        };
+
+        let data: &[u8] = b"3:abcX";
+        match block_on(decode(data, dec())) {
+            Err((_, DecodeError::DataError(CodecError::UnexpectedByte(b'X')))) => {}
+            _ => panic!("expected UnexpectedByte"),
+        }
+
+        let data: &[u8] = b"03:abc,";
+        match block_on(decode(data, dec())) {
+            Err((_, DecodeError::DataError(CodecError::InvalidLength))) => {}
+            _ => panic!("expected InvalidLength"),
+        }
+
+        let data: &[u8] = b"9:abcdefghi,";
+        match block_on(decode(data, dec())) {
+            Err((_, DecodeError::DataError(CodecError::TooLarge(8)))) => {}
+            _ => panic!("expected TooLarge"),
+        }
+    }
+}
@@ -0,0 +1,731 @@
+//! Field formats used by `#[derive(AsyncEncode, AsyncDecode)]`.
+//!
+//! Every field of a derived type is encoded according to a format, selected via a `#[codec(...)]`
+//! attribute on the field:
+//!
+//! - no attribute: `Auto`, implemented for `u8`, `i8`, `bool` and all derived types
+//! - `#[codec(be)]`, `#[codec(le)]`: `Be` and `Le`, fixed-width big and little endian numbers
+//! - `#[codec(varint)]`: `Varint`, LEB128 for unsigned and zigzag LEB128 for signed integers
+//! - `#[codec(len_prefix = "u16")]`: `LenPrefix<u16>`, a big-endian length followed by the bytes of
+//!   a `Vec<u8>` or `String` (`"u8"`, `"u32"` and `"u64"` work as well)
+//!
+//! Enums are encoded as a discriminant followed by the fields of the variant. The discriminant
+//! is a varint by default, this can be changed via `#[codec(tag = "u8")]` on the enum (`"u16"`,
+//! `"u32"` and `"u64"` are encoded big-endian). Explicit discriminants (`Variant = 42`) are
+//! respected.
+//!
+//! A derived type `Foo` gets the encoder `FooEncoder` and the decoder `FooDecoder`, obtained via
+//! `encoder(foo)` and `decoder::<Foo>()`. All derived decoders use `FieldError` as their error.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::marker::PhantomData;
+use std::mem::size_of;
+
+use async_codec::{AsyncDecode, AsyncEncode, AsyncEncodeLen, DecodeError, PollDec, PollEnc};
+use futures_core::Async::{Ready, Pending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, AsyncWrite, Error as FutIoErr, ErrorKind};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use async_codec::{AsyncDecode, AsyncEncode, AsyncEncodeLen, DecodeError, PollDec,
+                          PollEnc};
+    pub use futures_core::task::Context;
+    pub use futures_io::{AsyncRead, AsyncWrite};
+}
+
+/// Create the encoder for a value with a derived (or otherwise `Auto`) encoding, erroring if some
+/// field is too long for its length prefix.
+pub fn encoder<T: EncodeField<Auto>>(value: T) -> Result<T::Encoder, PrefixOverflow> {
+    value.field_encoder()
+}
+
+/// Create the decoder for a type with a derived (or otherwise `Auto`) encoding.
+pub fn decoder<T: DecodeField<Auto>>() -> T::Decoder {
+    T::field_decoder()
+}
+
+/// The default format, used for fields without a `#[codec(...)]` attribute.
+pub struct Auto;
+/// Fixed-width big-endian numbers.
+pub struct Be;
+/// Fixed-width little-endian numbers.
+pub struct Le;
+/// LEB128 varints, zigzag encoded for signed integers.
+pub struct Varint;
+/// Bytes or strings prefixed by their length, encoded as a big-endian `L`.
+pub struct LenPrefix<L>(PhantomData<L>);
+
+/// A type that can be encoded in the format `F`.
+pub trait EncodeField<F>: Sized {
+    /// The encoder for values of this type.
+    type Encoder: AsyncEncodeLen;
+
+    /// Create an encoder for this value, erroring if it (or one of its fields) is too long for its
+    /// length prefix.
+    fn field_encoder(self) -> Result<Self::Encoder, PrefixOverflow>;
+}
+
+/// A type that can be decoded from the format `F`.
+pub trait DecodeField<F>: Sized {
+    /// The decoder for values of this type.
+    type Decoder: AsyncDecode<Item = Self, Error = FieldError>;
+
+    /// Create a decoder for a value of this type.
+    fn field_decoder() -> Self::Decoder;
+}
+
+/// The error of all field decoders and derived decoders.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FieldError {
+    /// A varint did not fit into the target type.
+    VarintOverflow,
+    /// A length prefix did not fit into a `usize`.
+    LengthOverflow,
+    /// A bool was encoded as the contained byte rather than 0 or 1.
+    InvalidBool(u8),
+    /// A length-prefixed string was not valid UTF-8.
+    InvalidUtf8,
+    /// The discriminant of an enum did not match any variant.
+    InvalidDiscriminant(u64),
+}
+
+impl Display for FieldError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            FieldError::VarintOverflow => write!(f, "Varint overflows its type"),
+            FieldError::LengthOverflow => write!(f, "Length prefix overflows"),
+            FieldError::InvalidBool(byte) => write!(f, "Invalid bool {:#04x}", byte),
+            FieldError::InvalidUtf8 => write!(f, "String is not valid UTF-8"),
+            FieldError::InvalidDiscriminant(disc) => write!(f, "Invalid discriminant {}", disc),
+        }
+    }
+}
+
+impl Error for FieldError {}
+
+/// The error of creating a field encoder: bytes of the contained length do not fit into their
+/// length prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixOverflow(pub usize);
+
+impl Display for PrefixOverflow {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f, "Length {} does not fit into its length prefix", self.0)
+    }
+}
+
+impl Error for PrefixOverflow {}
+
+/// Encodes up to 16 bytes held inline, used for numbers and varints.
+pub struct FixedEncoder {
+    buf: [u8; 16],
+    len: usize,
+    offset: usize,
+}
+
+impl FixedEncoder {
+    fn new(bytes: &[u8]) -> FixedEncoder {
+        let mut buf = [0; 16];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        FixedEncoder {
+            buf,
+            len: bytes.len(),
+            offset: 0,
+        }
+    }
+
+    /// Create an encoder for `value` as an LEB128 varint.
+    pub fn varint(mut value: u64) -> FixedEncoder {
+        let mut buf = [0; 16];
+        let mut len = 0;
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                buf[len] = byte;
+                len += 1;
+                break;
+            } else {
+                buf[len] = byte | 0x80;
+                len += 1;
+            }
+        }
+
+        FixedEncoder {
+            buf,
+            len,
+            offset: 0,
+        }
+    }
+}
+
+impl AsyncEncode for FixedEncoder {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        if self.offset == self.len {
+            return PollEnc::Done(0);
+        }
+
+        match writer.poll_write(cx, &self.buf[self.offset..self.len]) {
+            Ok(Ready(0)) => PollEnc::Errored(FutIoErr::new(ErrorKind::WriteZero, "field")),
+            Ok(Ready(written)) => {
+                self.offset += written;
+                if self.offset == self.len {
+                    PollEnc::Done(written)
+                } else {
+                    PollEnc::Progress(self, written)
+                }
+            }
+            Ok(Pending) => PollEnc::Pending(self),
+            Err(err) => PollEnc::Errored(err),
+        }
+    }
+}
+
+impl AsyncEncodeLen for FixedEncoder {
+    fn remaining_bytes(&self) -> usize {
+        self.len - self.offset
+    }
+}
+
+/// Decodes a fixed number of bytes and converts them into a `T`.
+pub struct FixedDecoder<T> {
+    buf: [u8; 16],
+    len: usize,
+    read: usize,
+    convert: fn(&[u8]) -> Result<T, FieldError>,
+}
+
+impl<T> FixedDecoder<T> {
+    fn new(len: usize, convert: fn(&[u8]) -> Result<T, FieldError>) -> FixedDecoder<T> {
+        FixedDecoder {
+            buf: [0; 16],
+            len,
+            read: 0,
+            convert,
+        }
+    }
+}
+
+impl<T> AsyncDecode for FixedDecoder<T> {
+    type Item = T;
+    type Error = FieldError;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        match reader.poll_read(cx, &mut self.buf[self.read..self.len]) {
+            Ok(Ready(0)) => {
+                PollDec::Errored(DecodeError::ReaderError(FutIoErr::new(ErrorKind::UnexpectedEof,
+                                                                        "field")))
+            }
+            Ok(Ready(read)) => {
+                self.read += read;
+                if self.read < self.len {
+                    PollDec::Progress(self, read)
+                } else {
+                    match (self.convert)(&self.buf[..self.len]) {
+                        Ok(item) => PollDec::Done(item, read),
+                        Err(err) => PollDec::Errored(DecodeError::DataError(err)),
+                    }
+                }
+            }
+            Ok(Pending) => PollDec::Pending(self),
+            Err(err) => PollDec::Errored(DecodeError::ReaderError(err)),
+        }
+    }
+}
+
+/// Decodes a LEB128 varint and converts it into a `T`.
+///
+/// This reads a single byte per poll, so it never reads past the end of the varint.
+pub struct VarintDecoder<T> {
+    value: u64,
+    shift: u32,
+    convert: fn(u64) -> Option<T>,
+}
+
+impl<T> VarintDecoder<T> {
+    fn new(convert: fn(u64) -> Option<T>) -> VarintDecoder<T> {
+        VarintDecoder {
+            value: 0,
+            shift: 0,
+            convert,
+        }
+    }
+}
+
+impl<T> AsyncDecode for VarintDecoder<T> {
+    type Item = T;
+    type Error = FieldError;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        let mut byte = [0];
+        match reader.poll_read(cx, &mut byte) {
+            Ok(Ready(0)) => {
+                PollDec::Errored(DecodeError::ReaderError(FutIoErr::new(ErrorKind::UnexpectedEof,
+                                                                        "varint")))
+            }
+            Ok(Ready(_)) => {
+                let byte = byte[0];
+                if self.shift == 63 && byte & 0x7f > 1 {
+                    return PollDec::Errored(DecodeError::DataError(FieldError::VarintOverflow));
+                }
+                self.value |= u64::from(byte & 0x7f) << self.shift;
+
+                if byte & 0x80 == 0 {
+                    match (self.convert)(self.value) {
+                        Some(item) => PollDec::Done(item, 1),
+                        None => {
+                            PollDec::Errored(DecodeError::DataError(FieldError::VarintOverflow))
+                        }
+                    }
+                } else if self.shift == 63 {
+                    PollDec::Errored(DecodeError::DataError(FieldError::VarintOverflow))
+                } else {
+                    self.shift += 7;
+                    PollDec::Progress(self, 1)
+                }
+            }
+            Ok(Pending) => PollDec::Pending(self),
+            Err(err) => PollDec::Errored(DecodeError::ReaderError(err)),
+        }
+    }
+}
+
+/// Encodes bytes prefixed by their length.
+pub struct LenPrefixEncoder {
+    prefix: [u8; 8],
+    prefix_len: usize,
+    data: Vec<u8>,
+    // Counts the written bytes of the prefix followed by the data.
+    offset: usize,
+}
+
+impl LenPrefixEncoder {
+    fn new(prefix_len: usize, data: Vec<u8>) -> Result<LenPrefixEncoder, PrefixOverflow> {
+        let len = data.len() as u64;
+        if prefix_len < 8 && len >> (prefix_len * 8) != 0 {
+            return Err(PrefixOverflow(data.len()));
+        }
+
+        Ok(LenPrefixEncoder {
+               prefix: len.to_be_bytes(),
+               prefix_len,
+               data,
+               offset: 0,
+           })
+    }
+}
+
+impl AsyncEncode for LenPrefixEncoder {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        let res = if self.offset < self.prefix_len {
+            writer.poll_write(cx, &self.prefix[8 - self.prefix_len + self.offset..])
+        } else {
+            writer.poll_write(cx, &self.data[self.offset - self.prefix_len..])
+        };
+
+        match res {
+            Ok(Ready(0)) => PollEnc::Errored(FutIoErr::new(ErrorKind::WriteZero, "field")),
+            Ok(Ready(written)) => {
+                self.offset += written;
+                if self.offset == self.prefix_len + self.data.len() {
+                    PollEnc::Done(written)
+                } else {
+                    PollEnc::Progress(self, written)
+                }
+            }
+            Ok(Pending) => PollEnc::Pending(self),
+            Err(err) => PollEnc::Errored(err),
+        }
+    }
+}
+
+impl AsyncEncodeLen for LenPrefixEncoder {
+    fn remaining_bytes(&self) -> usize {
+        self.prefix_len + self.data.len() - self.offset
+    }
+}
+
+/// Decodes bytes prefixed by their length and converts them into a `T`.
+pub struct LenPrefixDecoder<T> {
+    // `None` once the length has been decoded.
+    len: Option<FixedDecoder<u64>>,
+    remaining: usize,
+    buf: Vec<u8>,
+    convert: fn(Vec<u8>) -> Result<T, FieldError>,
+}
+
+impl<T> LenPrefixDecoder<T> {
+    fn new(prefix_len: usize,
+           convert: fn(Vec<u8>) -> Result<T, FieldError>)
+           -> LenPrefixDecoder<T> {
+        LenPrefixDecoder {
+            len: Some(FixedDecoder::new(prefix_len, |bytes| {
+                Ok(bytes.iter().fold(0, |acc, byte| acc << 8 | u64::from(*byte)))
+            })),
+            remaining: 0,
+            buf: Vec::new(),
+            convert,
+        }
+    }
+}
+
+impl<T> AsyncDecode for LenPrefixDecoder<T> {
+    type Item = T;
+    type Error = FieldError;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        if let Some(len) = self.len.take() {
+            return match len.poll_decode(cx, reader) {
+                PollDec::Done(len, read) => {
+                    self.remaining = match usize::try_from(len) {
+                        Ok(len) => len,
+                        Err(_) => {
+                            let err = FieldError::LengthOverflow;
+                            return PollDec::Errored(DecodeError::DataError(err));
+                        }
+                    };
+
+                    if self.remaining == 0 {
+                        match (self.convert)(Vec::new()) {
+                            Ok(item) => PollDec::Done(item, read),
+                            Err(err) => PollDec::Errored(DecodeError::DataError(err)),
+                        }
+                    } else {
+                        PollDec::Progress(self, read)
+                    }
+                }
+                PollDec::Progress(len, read) => {
+                    self.len = Some(len);
+                    PollDec::Progress(self, read)
+                }
+                PollDec::Pending(len) => {
+                    self.len = Some(len);
+                    PollDec::Pending(self)
+                }
+                PollDec::Errored(err) => PollDec::Errored(err),
+            };
+        }
+
+        // Grow the buffer as data arrives, rather than trusting the length up front.
+        let mut chunk = [0; 1024];
+        let chunk_len = self.remaining.min(chunk.len());
+        match reader.poll_read(cx, &mut chunk[..chunk_len]) {
+            Ok(Ready(0)) => {
+                PollDec::Errored(DecodeError::ReaderError(FutIoErr::new(ErrorKind::UnexpectedEof,
+                                                                        "length prefixed")))
+            }
+            Ok(Ready(read)) => {
+                self.buf.extend_from_slice(&chunk[..read]);
+                self.remaining -= read;
+                if self.remaining == 0 {
+                    match (self.convert)(self.buf) {
+                        Ok(item) => PollDec::Done(item, read),
+                        Err(err) => PollDec::Errored(DecodeError::DataError(err)),
+                    }
+                } else {
+                    PollDec::Progress(self, read)
+                }
+            }
+            Ok(Pending) => PollDec::Pending(self),
+            Err(err) => PollDec::Errored(DecodeError::ReaderError(err)),
+        }
+    }
+}
+
+impl EncodeField<Auto> for u8 {
+    type Encoder = FixedEncoder;
+
+    fn field_encoder(self) -> Result<FixedEncoder, PrefixOverflow> {
+        Ok(FixedEncoder::new(&[self]))
+    }
+}
+
+impl DecodeField<Auto> for u8 {
+    type Decoder = FixedDecoder<u8>;
+
+    fn field_decoder() -> FixedDecoder<u8> {
+        FixedDecoder::new(1, |bytes| Ok(bytes[0]))
+    }
+}
+
+impl EncodeField<Auto> for i8 {
+    type Encoder = FixedEncoder;
+
+    fn field_encoder(self) -> Result<FixedEncoder, PrefixOverflow> {
+        Ok(FixedEncoder::new(&[self as u8]))
+    }
+}
+
+impl DecodeField<Auto> for i8 {
+    type Decoder = FixedDecoder<i8>;
+
+    fn field_decoder() -> FixedDecoder<i8> {
+        FixedDecoder::new(1, |bytes| Ok(bytes[0] as i8))
+    }
+}
+
+impl EncodeField<Auto> for bool {
+    type Encoder = FixedEncoder;
+
+    fn field_encoder(self) -> Result<FixedEncoder, PrefixOverflow> {
+        Ok(FixedEncoder::new(&[self as u8]))
+    }
+}
+
+impl DecodeField<Auto> for bool {
+    type Decoder = FixedDecoder<bool>;
+
+    fn field_decoder() -> FixedDecoder<bool> {
+        FixedDecoder::new(1, |bytes| match bytes[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            byte => Err(FieldError::InvalidBool(byte)),
+        })
+    }
+}
+
+macro_rules! fixed_width {
+    ($($t:ty),*) => {$(
+        impl EncodeField<Be> for $t {
+            type Encoder = FixedEncoder;
+
+            fn field_encoder(self) -> Result<FixedEncoder, PrefixOverflow> {
+                Ok(FixedEncoder::new(&self.to_be_bytes()))
+            }
+        }
+
+        impl DecodeField<Be> for $t {
+            type Decoder = FixedDecoder<$t>;
+
+            fn field_decoder() -> FixedDecoder<$t> {
+                FixedDecoder::new(size_of::<$t>(), |bytes| {
+                    let mut buf = [0; size_of::<$t>()];
+                    buf.copy_from_slice(bytes);
+                    Ok(<$t>::from_be_bytes(buf))
+                })
+            }
+        }
+
+        impl EncodeField<Le> for $t {
+            type Encoder = FixedEncoder;
+
+            fn field_encoder(self) -> Result<FixedEncoder, PrefixOverflow> {
+                Ok(FixedEncoder::new(&self.to_le_bytes()))
+            }
+        }
+
+        impl DecodeField<Le> for $t {
+            type Decoder = FixedDecoder<$t>;
+
+            fn field_decoder() -> FixedDecoder<$t> {
+                FixedDecoder::new(size_of::<$t>(), |bytes| {
+                    let mut buf = [0; size_of::<$t>()];
+                    buf.copy_from_slice(bytes);
+                    Ok(<$t>::from_le_bytes(buf))
+                })
+            }
+        }
+    )*}
+}
+
+fixed_width!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+macro_rules! varint_unsigned {
+    ($($t:ty),*) => {$(
+        impl EncodeField<Varint> for $t {
+            type Encoder = FixedEncoder;
+
+            fn field_encoder(self) -> Result<FixedEncoder, PrefixOverflow> {
+                Ok(FixedEncoder::varint(self as u64))
+            }
+        }
+
+        impl DecodeField<Varint> for $t {
+            type Decoder = VarintDecoder<$t>;
+
+            fn field_decoder() -> VarintDecoder<$t> {
+                VarintDecoder::new(|value| <$t>::try_from(value).ok())
+            }
+        }
+    )*}
+}
+
+varint_unsigned!(u16, u32, u64, usize);
+
+macro_rules! varint_signed {
+    ($($t:ty),*) => {$(
+        impl EncodeField<Varint> for $t {
+            type Encoder = FixedEncoder;
+
+            fn field_encoder(self) -> Result<FixedEncoder, PrefixOverflow> {
+                let value = self as i64;
+                Ok(FixedEncoder::varint(((value << 1) ^ (value >> 63)) as u64))
+            }
+        }
+
+        impl DecodeField<Varint> for $t {
+            type Decoder = VarintDecoder<$t>;
+
+            fn field_decoder() -> VarintDecoder<$t> {
+                VarintDecoder::new(|value| {
+                    let value = (value >> 1) as i64 ^ -((value & 1) as i64);
+                    <$t>::try_from(value).ok()
+                })
+            }
+        }
+    )*}
+}
+
+varint_signed!(i16, i32, i64, isize);
+
+macro_rules! len_prefix {
+    ($($l:ty),*) => {$(
+        impl EncodeField<LenPrefix<$l>> for Vec<u8> {
+            type Encoder = LenPrefixEncoder;
+
+            fn field_encoder(self) -> Result<LenPrefixEncoder, PrefixOverflow> {
+                LenPrefixEncoder::new(size_of::<$l>(), self)
+            }
+        }
+
+        impl DecodeField<LenPrefix<$l>> for Vec<u8> {
+            type Decoder = LenPrefixDecoder<Vec<u8>>;
+
+            fn field_decoder() -> LenPrefixDecoder<Vec<u8>> {
+                LenPrefixDecoder::new(size_of::<$l>(), Ok)
+            }
+        }
+
+        impl EncodeField<LenPrefix<$l>> for String {
+            type Encoder = LenPrefixEncoder;
+
+            fn field_encoder(self) -> Result<LenPrefixEncoder, PrefixOverflow> {
+                LenPrefixEncoder::new(size_of::<$l>(), self.into_bytes())
+            }
+        }
+
+        impl DecodeField<LenPrefix<$l>> for String {
+            type Decoder = LenPrefixDecoder<String>;
+
+            fn field_decoder() -> LenPrefixDecoder<String> {
+                LenPrefixDecoder::new(size_of::<$l>(), |bytes| {
+                    String::from_utf8(bytes).map_err(|_| FieldError::InvalidUtf8)
+                })
+            }
+        }
+    )*}
+}
+
+len_prefix!(u8, u16, u32, u64);
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use std::io::Cursor;
+
+    use async_codec::AsyncEncodeLen;
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use futures_executor::block_on;
+
+    use super::{decoder, encoder, EncodeField, FieldError, LenPrefix, PrefixOverflow};
+    use super::super::{AsyncDecode, AsyncEncode};
+    use super::super::{decode, encode};
+    use super::super::testing::test_codec_len;
+
+    #[derive(AsyncEncode, AsyncDecode, Debug, Clone, PartialEq)]
+    struct Header {
+        version: u8,
+        #[codec(be)]
+        id: u32,
+        #[codec(le)]
+        offset: i64,
+        #[codec(varint)]
+        count: u64,
+        #[codec(varint)]
+        delta: i32,
+        #[codec(len_prefix = "u16")]
+        name: String,
+        flag: bool,
+        kind: Kind,
+        unit: Unit,
+    }
+
+    #[derive(AsyncEncode, AsyncDecode, Debug, Clone, PartialEq)]
+    #[codec(tag = "u8")]
+    #[repr(u8)]
+    enum Kind {
+        Empty,
+        Pair(u8, #[codec(be)] u16),
+        Named {
+            #[codec(len_prefix = "u8")]
+            data: Vec<u8>,
+        },
+        Late = 42,
+    }
+
+    #[derive(AsyncEncode, AsyncDecode, Debug, Clone, PartialEq)]
+    struct Unit;
+
+    #[test]
+    fn invalid_discriminant() {
+        let data: &[u8] = &[3];
+        match block_on(decode(data, decoder::<Kind>())) {
+            Err((_, ::async_codec::DecodeError::DataError(err))) => {
+                assert_eq!(err, FieldError::InvalidDiscriminant(3))
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn len_prefix_too_long() {
+        let enc = <Vec<u8> as EncodeField<LenPrefix<u8>>>::field_encoder(vec![0; 255]).unwrap();
+        assert_eq!(enc.remaining_bytes(), 256);
+        let (writer, written) = block_on(encode(Cursor::new(Vec::new()), enc)).unwrap();
+        assert_eq!((writer.into_inner().len(), written), (256, 256));
+
+        let enc = <Vec<u8> as EncodeField<LenPrefix<u8>>>::field_encoder(vec![0; 256]);
+        assert_eq!(enc.err(), Some(PrefixOverflow(256)));
+
+        let data = vec![0; 256];
+        assert_eq!(encoder(Kind::Named { data }).err(), Some(PrefixOverflow(256)));
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, numbers: (u8, u32, i64, u64, i32), rest: (String, bool, u8, Vec<u8>)) -> bool {
+            let (version, id, offset, count, delta) = numbers;
+            let (name, flag, kind, data) = rest;
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let mut data = data;
+            data.truncate(255);
+            let kind = match kind % 4 {
+                0 => Kind::Empty,
+                1 => Kind::Pair(version, id as u16),
+                2 => Kind::Named { data },
+                _ => Kind::Late,
+            };
+            let header = Header { version, id, offset, count, delta, name, flag, kind, unit: Unit };
+
+            let enc = encoder(header.clone()).unwrap();
+            let test_outcome = test_codec_len(r, w, decoder::<Header>(), enc);
+            test_outcome.1 && test_outcome.0 == header
+        }
+    }
+}
@@ -8,24 +8,100 @@ extern crate futures_core;
 extern crate futures_io;
 extern crate futures_executor;
 extern crate futures_util;
+extern crate async_ringbuffer;
+#[cfg_attr(test, macro_use(quickcheck))]
+extern crate quickcheck;
+#[cfg(feature = "digest")]
+extern crate digest;
+#[cfg(feature = "flate2")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(feature = "box-stream")]
+extern crate crypto_secretbox;
+#[cfg(feature = "derive")]
+extern crate async_codec_util_derive;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate bincode;
+
+// Lets the derive macros refer to this crate by name in its own tests.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as async_codec_util;
 
 #[cfg(test)]
 extern crate async_byteorder;
-#[cfg(test)]
-extern crate async_ringbuffer;
-#[cfg(test)]
-#[macro_use(quickcheck)]
-extern crate quickcheck;
+#[cfg(all(test, feature = "digest"))]
+extern crate sha2;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
 
 pub mod encoder;
 pub mod decoder;
 pub mod testing;
+pub mod strict;
+pub mod netstring;
+pub mod checksum;
+pub mod sink;
+pub mod compression;
+pub mod field;
+#[cfg(feature = "box-stream")]
+pub mod box_stream;
+mod error;
+
+pub use error::CodecError;
+
+#[cfg(feature = "derive")]
+pub use async_codec_util_derive::{AsyncDecode, AsyncEncode};
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 
 use async_codec::{AsyncEncode, AsyncEncodeLen, AsyncDecode, DecodeError, PollEnc, PollDec};
 use futures_core::{Future, Poll};
 use futures_core::Async::{Ready, Pending};
 use futures_core::task::Context;
-use futures_io::{AsyncRead, AsyncWrite, Error as FutIoErr};
+use futures_io::{AsyncRead, AsyncWrite, Error as FutIoErr, ErrorKind};
+
+/// The number of consecutive `Progress(_, 0)` results after which a codec is considered to be
+/// stuck in an infinite loop.
+pub const MAX_IDLE_PROGRESS: usize = 4096;
+
+/// A codec returned `Progress(_, 0)` more than `MAX_IDLE_PROGRESS` times in a row.
+///
+/// `Encoder` and `Decoder` fail with an io error of kind `Other` wrapping this, use
+/// `NoProgress::is` to tell it apart from other io errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoProgress;
+
+impl NoProgress {
+    /// Return whether the given io error wraps a `NoProgress`.
+    // `Option::is_some_and` would need Rust 1.70.
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn is(err: &FutIoErr) -> bool {
+        err.get_ref().map_or(false, |inner| inner.is::<NoProgress>())
+    }
+}
+
+// `io::Error::other` would need Rust 1.74.
+#[allow(clippy::io_other_error)]
+impl From<NoProgress> for FutIoErr {
+    fn from(err: NoProgress) -> FutIoErr {
+        FutIoErr::new(ErrorKind::Other, err)
+    }
+}
+
+impl Display for NoProgress {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(f,
+               "Codec made no progress in {} consecutive polls",
+               MAX_IDLE_PROGRESS)
+    }
+}
+
+impl Error for NoProgress {}
 
 /// Encode a value into an `AsyncWrite`, using an `AsyncEncode`.
 pub fn encode<W, C>(writer: W, co: C) -> Encoder<W, C> {
@@ -48,6 +124,14 @@ impl<W, C> Encoder<W, C> {
             written: 0,
         }
     }
+
+    /// Return the number of bytes written so far.
+    ///
+    /// This is the absolute offset at which an encoder for the same writer would start, e.g. for
+    /// an `encoder::Aligned`.
+    pub fn bytes_written(&self) -> usize {
+        self.written
+    }
 }
 
 impl<W, C> Encoder<W, C>
@@ -78,24 +162,33 @@ impl<W, C> Future for Encoder<W, C>
         let mut writer = self.writer
             .take()
             .expect("Polled encoder future after completion");
-        let enc = self.enc
+        let mut enc = self.enc
             .take()
             .expect("Polled encoder future after completion");
+        let mut idle = 0;
 
-        match enc.poll_encode(cx, &mut writer) {
-            PollEnc::Done(written) => Ok(Ready((writer, self.written + written))),
-            PollEnc::Progress(enc, written) => {
-                self.written += written;
-                self.writer = Some(writer);
-                self.enc = Some(enc);
-                self.poll(cx)
+        loop {
+            match enc.poll_encode(cx, &mut writer) {
+                PollEnc::Done(written) => return Ok(Ready((writer, self.written + written))),
+                PollEnc::Progress(new_enc, written) => {
+                    self.written += written;
+                    if written == 0 {
+                        idle += 1;
+                        if idle > MAX_IDLE_PROGRESS {
+                            return Err((writer, FutIoErr::from(NoProgress)));
+                        }
+                    } else {
+                        idle = 0;
+                    }
+                    enc = new_enc;
+                }
+                PollEnc::Pending(enc) => {
+                    self.writer = Some(writer);
+                    self.enc = Some(enc);
+                    return Ok(Pending);
+                }
+                PollEnc::Errored(err) => return Err((writer, err)),
             }
-            PollEnc::Pending(enc) => {
-                self.writer = Some(writer);
-                self.enc = Some(enc);
-                Ok(Pending)
-            }
-            PollEnc::Errored(err) => Err((writer, err)),
         }
     }
 }
@@ -121,6 +214,14 @@ impl<R, D> Decoder<R, D> {
             read: 0,
         }
     }
+
+    /// Return the number of bytes read so far.
+    ///
+    /// This is the absolute offset at which a decoder for the same reader would start, e.g. to
+    /// create a `sink::Position` for it.
+    pub fn bytes_read(&self) -> usize {
+        self.read
+    }
 }
 
 impl<R, D> Future for Decoder<R, D>
@@ -134,24 +235,34 @@ impl<R, D> Future for Decoder<R, D>
         let mut reader = self.reader
             .take()
             .expect("Polled decoder future after completion");
-        let dec = self.dec
+        let mut dec = self.dec
             .take()
             .expect("Polled decoder future after completion");
+        let mut idle = 0;
 
-        match dec.poll_decode(cx, &mut reader) {
-            PollDec::Done(item, read) => Ok(Ready((reader, item, self.read + read))),
-            PollDec::Progress(dec, read) => {
-                self.read += read;
-                self.reader = Some(reader);
-                self.dec = Some(dec);
-                self.poll(cx)
-            }
-            PollDec::Pending(dec) => {
-                self.reader = Some(reader);
-                self.dec = Some(dec);
-                Ok(Pending)
+        loop {
+            match dec.poll_decode(cx, &mut reader) {
+                PollDec::Done(item, read) => return Ok(Ready((reader, item, self.read + read))),
+                PollDec::Progress(new_dec, read) => {
+                    self.read += read;
+                    if read == 0 {
+                        idle += 1;
+                        if idle > MAX_IDLE_PROGRESS {
+                            let err = FutIoErr::from(NoProgress);
+                            return Err((reader, DecodeError::ReaderError(err)));
+                        }
+                    } else {
+                        idle = 0;
+                    }
+                    dec = new_dec;
+                }
+                PollDec::Pending(dec) => {
+                    self.reader = Some(reader);
+                    self.dec = Some(dec);
+                    return Ok(Pending);
+                }
+                PollDec::Errored(err) => return Err((reader, err)),
             }
-            PollDec::Errored(err) => Err((reader, err)),
         }
     }
 }
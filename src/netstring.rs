@@ -0,0 +1,371 @@
+//! Encoding and decoding of [netstrings](https://cr.yp.to/proto/netstrings.txt).
+//!
+//! A netstring is the ASCII decimal length of some data, followed by a `:`, the data itself and a
+//! trailing `,`, e.g. `12:hello world!,`.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+use async_codec::{AsyncDecode, AsyncEncode, AsyncEncodeLen, DecodeError, PollDec, PollEnc};
+use futures_core::Async::{Ready, Pending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, AsyncWrite, Error as FutIoErr, ErrorKind};
+
+use decoder::{DecodeExact, DecodeExactError};
+
+/// Create a `DecodeNetstring`, decoding the data of a netstring of at most `max_len` bytes via
+/// the given decoder.
+pub fn decode<D>(dec: D, max_len: usize) -> DecodeNetstring<D>
+    where D: AsyncDecode
+{
+    DecodeNetstring::new(dec, max_len)
+}
+
+/// Create an `EncodeNetstring`, encoding the output of the given encoder as a netstring.
+pub fn encode<C: AsyncEncodeLen>(enc: C) -> EncodeNetstring<C> {
+    EncodeNetstring::new(enc)
+}
+
+/// The error of a `DecodeNetstring`.
+#[derive(Debug)]
+pub enum NetstringError<E, I> {
+    /// The length was not terminated by a `:`, but by the contained byte.
+    InvalidLength(u8),
+    /// The length had a superfluous leading zero.
+    LeadingZero,
+    /// The length does not fit into a `usize`.
+    Overflow,
+    /// The length exceeded the contained maximum.
+    TooLong(usize),
+    /// The data was not followed by a `,`, but by the contained byte.
+    MissingComma(u8),
+    /// Decoding the data errored.
+    Data(DecodeExactError<E, I>),
+}
+
+impl<E: Display, I> Display for NetstringError<E, I> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            NetstringError::InvalidLength(byte) => {
+                write!(f, "Invalid byte {:#04x} in netstring length", byte)
+            }
+            NetstringError::LeadingZero => write!(f, "Netstring length has a leading zero"),
+            NetstringError::Overflow => write!(f, "Netstring length overflows"),
+            NetstringError::TooLong(max_len) => {
+                write!(f, "Netstring length exceeds maximum of {} bytes", max_len)
+            }
+            NetstringError::MissingComma(byte) => {
+                write!(f, "Expected trailing comma of netstring, got {:#04x}", byte)
+            }
+            NetstringError::Data(ref err) => write!(f, "Netstring data error: {}", err),
+        }
+    }
+}
+
+impl<E: Error + 'static, I: fmt::Debug + 'static> Error for NetstringError<E, I> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            NetstringError::Data(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn data_error<T, S, E, I>(err: NetstringError<E, I>) -> PollDec<T, S, NetstringError<E, I>> {
+    PollDec::Errored(DecodeError::DataError(err))
+}
+
+enum DecState<D>
+    where D: AsyncDecode
+{
+    // The length parsed so far and the number of digits it consisted of.
+    Length(D, usize, usize),
+    Data(DecodeExact<D>),
+    Comma(D::Item),
+}
+
+/// Decodes a netstring, delegating decoding of its data to a wrapped decoder.
+///
+/// The wrapped decoder must consume exactly the data of the netstring.
+pub struct DecodeNetstring<D>
+    where D: AsyncDecode
+{
+    state: DecState<D>,
+    max_len: usize,
+}
+
+impl<D> DecodeNetstring<D>
+    where D: AsyncDecode
+{
+    /// Create a new `DecodeNetstring`, decoding the data of a netstring of at most `max_len` bytes
+    /// via the given decoder.
+    pub fn new(dec: D, max_len: usize) -> DecodeNetstring<D> {
+        DecodeNetstring {
+            state: DecState::Length(dec, 0, 0),
+            max_len,
+        }
+    }
+}
+
+impl<D> AsyncDecode for DecodeNetstring<D>
+    where D: AsyncDecode
+{
+    type Item = D::Item;
+    type Error = NetstringError<D::Error, D::Item>;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        match self.state {
+            DecState::Length(dec, len, digits) => {
+                let mut byte = [0];
+                match reader.poll_read(cx, &mut byte) {
+                    Ok(Ready(0)) => {
+                        PollDec::Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "netstring")
+                                             .into())
+                    }
+                    Ok(Ready(_)) => {
+                        match byte[0] {
+                            b':' if digits > 0 => {
+                                self.state = DecState::Data(DecodeExact::new(dec, len));
+                                PollDec::Progress(self, 1)
+                            }
+                            b'0'..=b'9' => {
+                                if digits == 1 && len == 0 {
+                                    return data_error(NetstringError::LeadingZero);
+                                }
+
+                                let digit = (byte[0] - b'0') as usize;
+                                match len.checked_mul(10).and_then(|len| len.checked_add(digit)) {
+                                    None => data_error(NetstringError::Overflow),
+                                    Some(len) if len > self.max_len => {
+                                        data_error(NetstringError::TooLong(self.max_len))
+                                    }
+                                    Some(len) => {
+                                        self.state = DecState::Length(dec, len, digits + 1);
+                                        PollDec::Progress(self, 1)
+                                    }
+                                }
+                            }
+                            other => data_error(NetstringError::InvalidLength(other)),
+                        }
+                    }
+                    Ok(Pending) => {
+                        self.state = DecState::Length(dec, len, digits);
+                        PollDec::Pending(self)
+                    }
+                    Err(err) => PollDec::Errored(err.into()),
+                }
+            }
+
+            DecState::Data(dec) => {
+                match dec.poll_decode(cx, reader) {
+                    PollDec::Done(item, read) => {
+                        self.state = DecState::Comma(item);
+                        PollDec::Progress(self, read)
+                    }
+                    PollDec::Progress(dec, read) => {
+                        self.state = DecState::Data(dec);
+                        PollDec::Progress(self, read)
+                    }
+                    PollDec::Pending(dec) => {
+                        self.state = DecState::Data(dec);
+                        PollDec::Pending(self)
+                    }
+                    PollDec::Errored(DecodeError::DataError(err)) => {
+                        data_error(NetstringError::Data(err))
+                    }
+                    PollDec::Errored(DecodeError::ReaderError(err)) => {
+                        PollDec::Errored(DecodeError::ReaderError(err))
+                    }
+                }
+            }
+
+            DecState::Comma(item) => {
+                let mut byte = [0];
+                match reader.poll_read(cx, &mut byte) {
+                    Ok(Ready(0)) => {
+                        PollDec::Errored(FutIoErr::new(ErrorKind::UnexpectedEof, "netstring")
+                                             .into())
+                    }
+                    Ok(Ready(_)) => {
+                        if byte[0] == b',' {
+                            PollDec::Done(item, 1)
+                        } else {
+                            data_error(NetstringError::MissingComma(byte[0]))
+                        }
+                    }
+                    Ok(Pending) => {
+                        self.state = DecState::Comma(item);
+                        PollDec::Pending(self)
+                    }
+                    Err(err) => PollDec::Errored(err.into()),
+                }
+            }
+        }
+    }
+}
+
+enum EncState<C> {
+    // The encoded length and how much of it has been written.
+    Length(C, Vec<u8>, usize),
+    Data(C),
+    Comma,
+}
+
+/// Encodes the output of a wrapped encoder as a netstring.
+pub struct EncodeNetstring<C>(EncState<C>);
+
+impl<C: AsyncEncodeLen> EncodeNetstring<C> {
+    /// Create a new `EncodeNetstring`, encoding the output of the given encoder as a netstring.
+    pub fn new(enc: C) -> EncodeNetstring<C> {
+        let len = format!("{}:", enc.remaining_bytes()).into_bytes();
+        EncodeNetstring(EncState::Length(enc, len, 0))
+    }
+}
+
+impl<C: AsyncEncodeLen> AsyncEncode for EncodeNetstring<C> {
+    fn poll_encode<W: AsyncWrite>(mut self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+        match self.0 {
+            EncState::Length(enc, len, offset) => {
+                match writer.poll_write(cx, &len[offset..]) {
+                    Ok(Ready(0)) => {
+                        PollEnc::Errored(FutIoErr::new(ErrorKind::WriteZero, "netstring"))
+                    }
+                    Ok(Ready(written)) => {
+                        if offset + written < len.len() {
+                            self.0 = EncState::Length(enc, len, offset + written);
+                        } else if enc.remaining_bytes() == 0 {
+                            self.0 = EncState::Comma;
+                        } else {
+                            self.0 = EncState::Data(enc);
+                        }
+                        PollEnc::Progress(self, written)
+                    }
+                    Ok(Pending) => {
+                        self.0 = EncState::Length(enc, len, offset);
+                        PollEnc::Pending(self)
+                    }
+                    Err(err) => PollEnc::Errored(err),
+                }
+            }
+
+            EncState::Data(enc) => {
+                match enc.poll_encode(cx, writer) {
+                    PollEnc::Done(written) => {
+                        self.0 = EncState::Comma;
+                        PollEnc::Progress(self, written)
+                    }
+                    PollEnc::Progress(enc, written) => {
+                        self.0 = EncState::Data(enc);
+                        PollEnc::Progress(self, written)
+                    }
+                    PollEnc::Pending(enc) => {
+                        self.0 = EncState::Data(enc);
+                        PollEnc::Pending(self)
+                    }
+                    PollEnc::Errored(err) => PollEnc::Errored(err),
+                }
+            }
+
+            EncState::Comma => {
+                match writer.poll_write(cx, b",") {
+                    Ok(Ready(0)) => {
+                        PollEnc::Errored(FutIoErr::new(ErrorKind::WriteZero, "netstring"))
+                    }
+                    Ok(Ready(written)) => PollEnc::Done(written),
+                    Ok(Pending) => PollEnc::Pending(self),
+                    Err(err) => PollEnc::Errored(err),
+                }
+            }
+        }
+    }
+}
+
+impl<C: AsyncEncodeLen> AsyncEncodeLen for EncodeNetstring<C> {
+    fn remaining_bytes(&self) -> usize {
+        match self.0 {
+            EncState::Length(ref enc, ref len, offset) => {
+                len.len() - offset + enc.remaining_bytes() + 1
+            }
+            EncState::Data(ref enc) => enc.remaining_bytes() + 1,
+            EncState::Comma => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_codec::DecodeError;
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use async_byteorder::{decode_u16_be, encode_u16_be, decode_u64_native, encode_u64_native};
+    use futures_executor::block_on;
+
+    use super::*;
+    use super::super::decode as run_decode;
+    use super::super::testing::test_codec_len;
+
+    #[test]
+    fn wire_format() {
+        let data: &[u8] = b"2:\x01\x02,";
+        let (_, item, read) = block_on(run_decode(data, decode(decode_u16_be(), 16)))
+            .map_err(|(_, err)| err)
+            .unwrap();
+        assert_eq!(item, 0x0102);
+        assert_eq!(read, 5);
+        assert_eq!(encode(encode_u16_be(0x0102)).remaining_bytes(), 5);
+    }
+
+    #[test]
+    fn invalid_lengths() {
+        fn check(data: &[u8]) -> NetstringError<::futures_core::Never, u16> {
+            match block_on(run_decode(data, decode(decode_u16_be(), 1000))) {
+                Err((_, DecodeError::DataError(err))) => err,
+                _ => panic!("expected a data error"),
+            }
+        }
+
+        match check(b"02:ab,") {
+            NetstringError::LeadingZero => {}
+            err => panic!("{:?}", err),
+        }
+        match check(b":ab,") {
+            NetstringError::InvalidLength(b':') => {}
+            err => panic!("{:?}", err),
+        }
+        match check(b"1001:") {
+            NetstringError::TooLong(1000) => {}
+            err => panic!("{:?}", err),
+        }
+        match check(b"2:ab;") {
+            NetstringError::MissingComma(b';') => {}
+            err => panic!("{:?}", err),
+        }
+        match check(b"3:abc,") {
+            NetstringError::Data(DecodeExactError::Early(_, 2)) => {}
+            err => panic!("{:?}", err),
+        }
+
+        let overflow = format!("{}0:", usize::MAX);
+        let dec = decode(decode_u16_be(), usize::MAX);
+        match block_on(run_decode(overflow.as_bytes(), dec)) {
+            Err((_, DecodeError::DataError(NetstringError::Overflow))) => {}
+            _ => panic!("expected an overflow error"),
+        }
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, int: u64) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let test_outcome = test_codec_len(r, w, decode(decode_u64_native(), 8), encode(encode_u64_native(int)));
+            test_outcome.1 && test_outcome.0 == int
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Destinations for the bytes observed by `decoder::Tee` and `encoder::Tee`.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use checksum::{Adler32, Checksum, Crc32, Crc32c};
+
+/// Something that can be fed a sequence of bytes.
+pub trait ByteSink {
+    /// Feed some bytes into the sink.
+    fn consume(&mut self, bytes: &[u8]);
+}
+
+/// Captures a raw copy of all bytes.
+impl ByteSink for Vec<u8> {
+    fn consume(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+}
+
+impl<S: ByteSink + ?Sized> ByteSink for &mut S {
+    fn consume(&mut self, bytes: &[u8]) {
+        (**self).consume(bytes)
+    }
+}
+
+impl ByteSink for Crc32 {
+    fn consume(&mut self, bytes: &[u8]) {
+        self.update(bytes)
+    }
+}
+
+impl ByteSink for Crc32c {
+    fn consume(&mut self, bytes: &[u8]) {
+        self.update(bytes)
+    }
+}
+
+impl ByteSink for Adler32 {
+    fn consume(&mut self, bytes: &[u8]) {
+        self.update(bytes)
+    }
+}
+
+/// A `ByteSink` that counts the bytes it is fed, sharing the count with all its clones.
+///
+/// Wrap a whole decoder or encoder in a `Tee` feeding a `Position`, and hand clones of it to the
+/// `decoder::Context`s and `decoder::Aligned`s inside, so that they work with absolute offsets
+/// in the stream no matter how they are composed. Bytes that an `Or` replays to its second
+/// alternative are not counted again, so offsets inside the second alternative are too large.
+#[derive(Clone, Debug, Default)]
+pub struct Position(Arc<AtomicUsize>);
+
+impl Position {
+    /// Create a new `Position` starting at `offset`, e.g. the number of bytes already read from
+    /// the same reader.
+    pub fn new(offset: usize) -> Position {
+        Position(Arc::new(AtomicUsize::new(offset)))
+    }
+
+    /// The number of bytes counted so far, plus the initial offset.
+    pub fn get(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl ByteSink for Position {
+    fn consume(&mut self, bytes: &[u8]) {
+        self.0.fetch_add(bytes.len(), Ordering::SeqCst);
+    }
+}
+
+/// Create a `Callback`, feeding all bytes to the given function.
+pub fn callback<F: FnMut(&[u8])>(f: F) -> Callback<F> {
+    Callback(f)
+}
+
+/// A `ByteSink` that calls a function with all bytes it is fed.
+pub struct Callback<F>(pub F);
+
+impl<F: FnMut(&[u8])> ByteSink for Callback<F> {
+    fn consume(&mut self, bytes: &[u8]) {
+        (self.0)(bytes)
+    }
+}
+
+/// A `ByteSink` that feeds all bytes into an implementation of `digest::Digest`.
+#[cfg(feature = "digest")]
+pub struct DigestSink<D>(pub D);
+
+#[cfg(feature = "digest")]
+impl<D: ::digest::Digest> ByteSink for DigestSink<D> {
+    fn consume(&mut self, bytes: &[u8]) {
+        self.0.update(bytes)
+    }
+}
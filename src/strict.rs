@@ -0,0 +1,330 @@
+//! Drivers like `encode` and `decode` that additionally check that the codec adheres to the
+//! protocol of the async-codec traits, for debugging codec implementations.
+//!
+//! On top of detecting codecs that make no progress, these panic if a codec
+//!
+//! - reports a different number of bytes than the reader or writer actually transferred in a poll,
+//! - returns `Pending` in a poll in which the reader or writer did not (so nothing will wake the
+//!   task), or
+//! - returns anything but `Errored` in a poll in which the reader or writer errored.
+
+use async_codec::{AsyncEncode, AsyncDecode, DecodeError, PollEnc, PollDec};
+use futures_core::{Future, Poll};
+use futures_core::Async::{Ready, Pending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, AsyncWrite, Error as FutIoErr};
+
+use super::{NoProgress, MAX_IDLE_PROGRESS};
+
+// Records what happened to a reader or writer during a single poll of a codec.
+struct Monitor<T> {
+    inner: T,
+    transferred: usize,
+    pending: bool,
+    errored: bool,
+}
+
+impl<T> Monitor<T> {
+    fn new(inner: T) -> Monitor<T> {
+        Monitor {
+            inner,
+            transferred: 0,
+            pending: false,
+            errored: false,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.transferred = 0;
+        self.pending = false;
+        self.errored = false;
+    }
+
+    fn record(&mut self, res: &Poll<usize, FutIoErr>) {
+        match *res {
+            Ok(Ready(n)) => self.transferred += n,
+            Ok(Pending) => self.pending = true,
+            Err(_) => self.errored = true,
+        }
+    }
+
+    // Panics if the codec's report of a poll does not match what happened.
+    fn check(&self, what: &str, reported: Option<usize>, pending: bool) {
+        if let Some(reported) = reported {
+            assert!(reported == self.transferred,
+                    "Protocol violation: {} reported {} bytes but transferred {}",
+                    what,
+                    reported,
+                    self.transferred);
+        }
+        assert!(!pending || self.pending,
+                "Protocol violation: {} returned Pending without a registered wakeup",
+                what);
+        assert!(!self.errored,
+                "Protocol violation: {} did not error although the underlying io errored",
+                what);
+    }
+}
+
+impl<R: AsyncRead> AsyncRead for Monitor<R> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        let res = self.inner.poll_read(cx, buf);
+        self.record(&res);
+        res
+    }
+}
+
+impl<W: AsyncWrite> AsyncWrite for Monitor<W> {
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<usize, FutIoErr> {
+        let res = self.inner.poll_write(cx, buf);
+        self.record(&res);
+        res
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.inner.poll_close(cx)
+    }
+}
+
+/// Encode a value into an `AsyncWrite`, using an `AsyncEncode`, checking the encoder's behaviour.
+pub fn encode<W, C>(writer: W, co: C) -> Encoder<W, C> {
+    Encoder::new(writer, co)
+}
+
+/// Future for fully encoding an `AsyncEncode` into an `AsyncWrite`, panicking if the encoder
+/// violates the protocol.
+pub struct Encoder<W, C> {
+    writer: Option<Monitor<W>>,
+    enc: Option<C>,
+    written: usize,
+}
+
+impl<W, C> Encoder<W, C> {
+    /// Create a new `Encoder` wrapping an `AsyncWrite` and consuming an `AsyncEncode`.
+    pub fn new(writer: W, enc: C) -> Encoder<W, C> {
+        Encoder {
+            writer: Some(Monitor::new(writer)),
+            enc: Some(enc),
+            written: 0,
+        }
+    }
+}
+
+impl<W, C> Future for Encoder<W, C>
+    where W: AsyncWrite,
+          C: AsyncEncode
+{
+    type Item = (W, usize);
+    type Error = (W, FutIoErr);
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Item, Self::Error> {
+        let mut writer = self.writer
+            .take()
+            .expect("Polled encoder future after completion");
+        let mut enc = self.enc
+            .take()
+            .expect("Polled encoder future after completion");
+        let mut idle = 0;
+
+        loop {
+            writer.reset();
+            match enc.poll_encode(cx, &mut writer) {
+                PollEnc::Done(written) => {
+                    writer.check("encoder", Some(written), false);
+                    return Ok(Ready((writer.inner, self.written + written)));
+                }
+                PollEnc::Progress(new_enc, written) => {
+                    writer.check("encoder", Some(written), false);
+                    self.written += written;
+                    if written == 0 {
+                        idle += 1;
+                        if idle > MAX_IDLE_PROGRESS {
+                            return Err((writer.inner, FutIoErr::from(NoProgress)));
+                        }
+                    } else {
+                        idle = 0;
+                    }
+                    enc = new_enc;
+                }
+                PollEnc::Pending(enc) => {
+                    writer.check("encoder", None, true);
+                    self.writer = Some(writer);
+                    self.enc = Some(enc);
+                    return Ok(Pending);
+                }
+                PollEnc::Errored(err) => return Err((writer.inner, err)),
+            }
+        }
+    }
+}
+
+/// Decode a value from an `AsyncRead`, using an `AsyncDecode`, checking the decoder's behaviour.
+pub fn decode<R, D>(reader: R, dec: D) -> Decoder<R, D> {
+    Decoder::new(reader, dec)
+}
+
+/// Future for fully decoding an `AsyncDecode` from an `AsyncRead`, panicking if the decoder
+/// violates the protocol.
+pub struct Decoder<R, D> {
+    reader: Option<Monitor<R>>,
+    dec: Option<D>,
+    read: usize,
+}
+
+impl<R, D> Decoder<R, D> {
+    /// Create a new `Decoder` wrapping an `AsyncRead` and consuming an `AsyncDecode`.
+    pub fn new(reader: R, dec: D) -> Decoder<R, D> {
+        Decoder {
+            reader: Some(Monitor::new(reader)),
+            dec: Some(dec),
+            read: 0,
+        }
+    }
+}
+
+impl<R, D> Future for Decoder<R, D>
+    where R: AsyncRead,
+          D: AsyncDecode
+{
+    type Item = (R, D::Item, usize);
+    type Error = (R, DecodeError<D::Error>);
+
+    fn poll(&mut self, cx: &mut Context) -> Poll<Self::Item, Self::Error> {
+        let mut reader = self.reader
+            .take()
+            .expect("Polled decoder future after completion");
+        let mut dec = self.dec
+            .take()
+            .expect("Polled decoder future after completion");
+        let mut idle = 0;
+
+        loop {
+            reader.reset();
+            match dec.poll_decode(cx, &mut reader) {
+                PollDec::Done(item, read) => {
+                    reader.check("decoder", Some(read), false);
+                    return Ok(Ready((reader.inner, item, self.read + read)));
+                }
+                PollDec::Progress(new_dec, read) => {
+                    reader.check("decoder", Some(read), false);
+                    self.read += read;
+                    if read == 0 {
+                        idle += 1;
+                        if idle > MAX_IDLE_PROGRESS {
+                            let err = FutIoErr::from(NoProgress);
+                            return Err((reader.inner, DecodeError::ReaderError(err)));
+                        }
+                    } else {
+                        idle = 0;
+                    }
+                    dec = new_dec;
+                }
+                PollDec::Pending(dec) => {
+                    reader.check("decoder", None, true);
+                    self.reader = Some(reader);
+                    self.dec = Some(dec);
+                    return Ok(Pending);
+                }
+                PollDec::Errored(err) => return Err((reader.inner, err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_codec::{AsyncDecode, DecodeError, PollDec};
+    use async_byteorder::{decode_u8, decode_u16_be, encode_u8, encode_u16_be};
+    use atm_io_utils::partial::*;
+    use async_ringbuffer::ring_buffer;
+    use futures_core::Never;
+    use futures_core::task::Context;
+    use futures_executor::block_on;
+    use futures_io::{AsyncRead, Error as FutIoErr, ErrorKind};
+    use futures_util::FutureExt;
+
+    use super::{decode, encode};
+    use super::super::{decoder, encoder, NoProgress};
+    use super::super::decode as lenient_decode;
+
+    // Returns `Progress(_, 0)` forever.
+    struct Stuck;
+
+    impl AsyncDecode for Stuck {
+        type Item = ();
+        type Error = Never;
+
+        fn poll_decode<R: AsyncRead>(self,
+                                     _: &mut Context,
+                                     _: &mut R)
+                                     -> PollDec<Self::Item, Self, Self::Error> {
+            PollDec::Progress(self, 0)
+        }
+    }
+
+    // Reads a byte but claims to have read two.
+    struct Overreporting;
+
+    impl AsyncDecode for Overreporting {
+        type Item = u8;
+        type Error = Never;
+
+        fn poll_decode<R: AsyncRead>(self,
+                                     cx: &mut Context,
+                                     reader: &mut R)
+                                     -> PollDec<Self::Item, Self, Self::Error> {
+            match decode_u8().poll_decode(cx, reader) {
+                PollDec::Done(item, read) => PollDec::Done(item, read + 1),
+                PollDec::Progress(_, read) => PollDec::Progress(self, read),
+                PollDec::Pending(_) => PollDec::Pending(self),
+                PollDec::Errored(err) => PollDec::Errored(err),
+            }
+        }
+    }
+
+    #[test]
+    fn no_progress() {
+        let data: &[u8] = &[];
+        for res in [block_on(lenient_decode(data, Stuck)), block_on(decode(data, Stuck))] {
+            match res {
+                Err((_, DecodeError::ReaderError(err))) => {
+                    assert_eq!(err.kind(), ErrorKind::Other);
+                    assert!(NoProgress::is(&err));
+                }
+                _ => panic!("expected NoProgress error"),
+            }
+        }
+        assert!(!NoProgress::is(&FutIoErr::from(ErrorKind::Other)));
+        assert!(!NoProgress::is(&FutIoErr::new(ErrorKind::InvalidData, "stuck")));
+    }
+
+    #[test]
+    #[should_panic(expected = "decoder reported 2 bytes but transferred 1")]
+    fn overreporting() {
+        let data: &[u8] = &[42];
+        let _ = block_on(decode(data, Overreporting));
+    }
+
+    quickcheck! {
+        fn codec(buf_size: usize, read_ops: Vec<PartialOp>, write_ops: Vec<PartialOp>, a: u8, b: u16) -> bool {
+            let mut read_ops = read_ops;
+            let mut write_ops = write_ops;
+            let (w, r) = ring_buffer(buf_size + 1);
+            let w = PartialWrite::new(w, write_ops.drain(..));
+            let r = PartialRead::new(r, read_ops.drain(..));
+
+            let c = encode(w, encoder::chain(encode_u8(a), encode_u16_be(b)))
+                .map(|(_, written)| written)
+                .map_err(|(_, err)| panic!("{:?}", err));
+            let d = decode(r, decoder::chain(decode_u8(), decode_u16_be()))
+                .map(|(_, item, read)| (item, read))
+                .map_err(|(_, err)| panic!("{:?}", err));
+            let (written, (item, read)) = block_on(c.join(d)).map_err(|_: ()| ()).unwrap();
+            written == read && item == (a, b)
+        }
+    }
+}
@@ -1,14 +1,28 @@
 //! Helpers for testing implementations of the async-codec traits.
 
-use std::fmt::Debug;
+use std::any::Any;
+use std::env;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::fs;
+use std::io::Cursor;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 
-use async_codec::{AsyncDecode, DecodeError, AsyncEncode, AsyncEncodeLen};
-use futures_io::{AsyncRead, AsyncWrite};
+use async_codec::{AsyncDecode, DecodeError, AsyncEncode, AsyncEncodeLen, PollDec, PollEnc};
+use async_ringbuffer::ring_buffer;
+use atm_io_utils::partial::{PartialOp, PartialRead, PartialWrite};
+use futures_core::{Never, Poll};
+use futures_core::Async::{Ready, Pending};
+use futures_core::task::Context;
+use futures_io::{AsyncRead, AsyncWrite, Error as FutIoErr};
 use futures_io::ErrorKind::{UnexpectedEof, WriteZero};
 use futures_executor::block_on;
 use futures_util::FutureExt;
+use futures_util::future::poll_fn;
+use quickcheck::{Arbitrary, Gen, QuickCheck, Testable, TestResult};
 
-use super::{decode, encode};
+use super::{decode, encode, MAX_IDLE_PROGRESS};
 
 /// Returns whether the given decoder returns an error of kind `UnexpectedEof` when trying to fully
 /// decode from the given reader.
@@ -33,6 +47,9 @@ pub fn write_zero_errors<W: AsyncWrite, C: AsyncEncode>(writer: W, co: C) -> boo
     }
 }
 
+// The item (if any) and the byte count produced by one side of a codec test.
+type Side<T> = (Option<T>, usize);
+
 fn test_codec_intern<R: AsyncRead, W: AsyncWrite, D: AsyncDecode, C: AsyncEncode>
     (reader: R,
      writer: W,
@@ -49,8 +66,7 @@ fn test_codec_intern<R: AsyncRead, W: AsyncWrite, D: AsyncDecode, C: AsyncEncode
     let d = d.map(|(_, item, read)| (Some(item), read))
         .map_err(|(_, err)| panic!("{:?}", err));
     block_on(c.join(d)
-                 .map(|((_, written), (item, read)): ((Option<D::Item>, usize),
-                                                      (Option<D::Item>, usize))| {
+                 .map(|((_, written), (item, read)): (Side<D::Item>, Side<D::Item>)| {
                           let item = item.unwrap();
                           (item, written, read)
                       }))
@@ -85,3 +101,1186 @@ pub fn test_codec_len<R: AsyncRead, W: AsyncWrite, D: AsyncDecode, C: AsyncEncod
     let (item, written, read) = test_codec_intern(reader, writer, dec, co);
     (item, written == read && written == expected_len)
 }
+
+// Records all bytes read or written through it.
+struct Recorder<T> {
+    inner: T,
+    bytes: Vec<u8>,
+}
+
+impl<T> Recorder<T> {
+    fn new(inner: T) -> Recorder<T> {
+        Recorder {
+            inner,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for Recorder<T> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        let res = self.inner.poll_read(cx, buf);
+        if let Ok(Ready(read)) = res {
+            self.bytes.extend_from_slice(&buf[..read]);
+        }
+        res
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for Recorder<T> {
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<usize, FutIoErr> {
+        let res = self.inner.poll_write(cx, buf);
+        if let Ok(Ready(written)) = res {
+            self.bytes.extend_from_slice(&buf[..written]);
+        }
+        res
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.inner.poll_close(cx)
+    }
+}
+
+/// The outcome of running an encoder and a decoder concurrently, see `test_codec_report`.
+///
+/// Its `Display` implementation lists all counts and errors, followed by a hexdump of the written
+/// and the read bytes in which differing lines are marked with a `!`.
+#[derive(Debug)]
+pub struct CodecTestReport<T, E> {
+    /// The decoded item, if the decoder finished.
+    pub item: Option<T>,
+    /// The bytes written by the encoder.
+    pub written: Vec<u8>,
+    /// The bytes read by the decoder.
+    pub read: Vec<u8>,
+    /// The number of bytes the encoder promised to write, if it implements `AsyncEncodeLen`.
+    pub promised: Option<usize>,
+    /// The error of the encoder, if any.
+    pub encoder_error: Option<FutIoErr>,
+    /// The error of the decoder, if any.
+    pub decoder_error: Option<DecodeError<E>>,
+}
+
+impl<T, E> CodecTestReport<T, E> {
+    /// Return whether both encoder and decoder finished without errors, with the decoder reading
+    /// exactly the bytes that were written, and the encoder writing as many bytes as it promised.
+    // `Option::is_none_or` would need Rust 1.82.
+    #[allow(clippy::unnecessary_map_or)]
+    pub fn is_ok(&self) -> bool {
+        self.item.is_some() && self.encoder_error.is_none() && self.decoder_error.is_none() &&
+        self.written == self.read &&
+        self.promised.map_or(true, |promised| promised == self.written.len())
+    }
+}
+
+fn hex_line(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+// Hexdumps two byte strings side by side, marking differing lines with a `!`.
+fn hexdump_diff(left: (&str, &[u8]), right: (&str, &[u8])) -> String {
+    let line_of = |bytes: &[u8], start: usize| -> Vec<u8> {
+        bytes.iter().skip(start).take(16).cloned().collect()
+    };
+
+    let mut dump = format!("  {:<8}  {:<47} | {}", "offset", left.0, right.0);
+    for start in (0..left.1.len().max(right.1.len())).step_by(16) {
+        let (l, r) = (line_of(left.1, start), line_of(right.1, start));
+        dump.push_str(&format!("\n{} {:08x}  {:<47} | {}",
+                               if l == r { " " } else { "!" },
+                               start,
+                               hex_line(&l),
+                               hex_line(&r)));
+    }
+    dump
+}
+
+// Parses hexadecimal bytes, ignoring whitespace.
+// `usize::is_multiple_of` would need Rust 1.87.
+#[allow(clippy::manual_is_multiple_of)]
+fn parse_hex(hex: &str) -> Vec<u8> {
+    let digits: Vec<u8> = hex.bytes().filter(|byte| !byte.is_ascii_whitespace()).collect();
+    assert!(digits.len() % 2 == 0, "Odd number of hex digits");
+    digits.chunks(2)
+        .map(|pair| {
+                 let pair = ::std::str::from_utf8(pair).expect("Invalid hex digit");
+                 u8::from_str_radix(pair, 16).expect("Invalid hex digit")
+             })
+        .collect()
+}
+
+// Fully encodes into a vector, panicking on errors.
+fn encode_to_vec<C: AsyncEncode>(enc: C) -> Vec<u8> {
+    match block_on(encode(Cursor::new(Vec::new()), enc)) {
+        Ok((writer, _)) => writer.into_inner(),
+        Err((_, err)) => panic!("Encoder errored: {:?}", err),
+    }
+}
+
+impl<T: Debug, E: Debug> Display for CodecTestReport<T, E> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        writeln!(f,
+                 "Codec test {}",
+                 if self.is_ok() { "succeeded" } else { "failed" })?;
+        if let Some(promised) = self.promised {
+            writeln!(f, "promised: {} bytes", promised)?;
+        }
+        writeln!(f, "written: {} bytes", self.written.len())?;
+        writeln!(f, "read: {} bytes", self.read.len())?;
+        if let Some(ref err) = self.encoder_error {
+            writeln!(f, "encoder error: {:?}", err)?;
+        }
+        if let Some(ref err) = self.decoder_error {
+            writeln!(f, "decoder error: {:?}", err)?;
+        }
+        if let Some(ref item) = self.item {
+            writeln!(f, "decoded item: {:?}", item)?;
+        }
+
+        write!(f, "{}", hexdump_diff(("written", &self.written), ("read", &self.read)))
+    }
+}
+
+fn test_codec_report_intern<R, W, D, C>(reader: R,
+                                        writer: W,
+                                        dec: D,
+                                        co: C,
+                                        promised: Option<usize>)
+                                        -> CodecTestReport<D::Item, D::Error>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          D: AsyncDecode,
+          C: AsyncEncode
+{
+    // The reader and writer are dropped as soon as their side is done, so that the other side
+    // does not wait for them forever.
+    let c = encode(Recorder::new(writer), co).then(|res| -> Result<_, Never> {
+        Ok(match res {
+               Ok((writer, _)) => (writer.bytes, None),
+               Err((writer, err)) => (writer.bytes, Some(err)),
+           })
+    });
+    let d = decode(Recorder::new(reader), dec).then(|res| -> Result<_, Never> {
+        Ok(match res {
+               Ok((reader, item, _)) => (reader.bytes, Some(item), None),
+               Err((reader, err)) => (reader.bytes, None, Some(err)),
+           })
+    });
+
+    let ((written, encoder_error), (read, item, decoder_error)) = block_on(c.join(d)).unwrap();
+    CodecTestReport {
+        item,
+        written,
+        read,
+        promised,
+        encoder_error,
+        decoder_error,
+    }
+}
+
+/// Run an encoder and a decoder concurrently, returning a report of their outcome instead of
+/// panicking on errors.
+pub fn test_codec_report<R, W, D, C>(reader: R,
+                                     writer: W,
+                                     dec: D,
+                                     co: C)
+                                     -> CodecTestReport<D::Item, D::Error>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          D: AsyncDecode,
+          C: AsyncEncode
+{
+    test_codec_report_intern(reader, writer, dec, co, None)
+}
+
+/// Run an encoder and a decoder concurrently, returning a report of their outcome, including the
+/// number of bytes the encoder promised to write.
+pub fn test_codec_len_report<R, W, D, C>(reader: R,
+                                         writer: W,
+                                         dec: D,
+                                         co: C)
+                                         -> CodecTestReport<D::Item, D::Error>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          D: AsyncDecode,
+          C: AsyncEncodeLen
+{
+    let promised = co.remaining_bytes();
+    test_codec_report_intern(reader, writer, dec, co, Some(promised))
+}
+
+// The inputs of a single round trip: buffer size, read ops, write ops and the value.
+type RoundtripInput<T> = (usize, Vec<PartialOp>, Vec<PartialOp>, T);
+
+// Runs a single round trip, describing the first problem found.
+fn roundtrip_once<T, C, D>(enc: C, dec: D, input: RoundtripInput<T>) -> Result<(), String>
+    where T: Debug + PartialEq,
+          C: AsyncEncodeLen,
+          D: AsyncDecode<Item = T>,
+          D::Error: Debug
+{
+    let (buf_size, read_ops, write_ops, value) = input;
+    let (w, r) = ring_buffer(buf_size + 1);
+    let w = PartialWrite::new(w, write_ops.into_iter());
+    let r = PartialRead::new(r, read_ops.into_iter());
+
+    let expected_len = enc.remaining_bytes();
+    let c = encode(w, enc)
+        .map(|(_, written)| written)
+        .map_err(|(_, err)| format!("Encoder errored: {}", err));
+    let d = decode(r, dec)
+        .map(|(_, item, read)| (item, read))
+        .map_err(|(_, err)| format!("Decoder errored: {:?}", err));
+    let (written, (item, read)) = block_on(c.join(d))?;
+
+    if item != value {
+        Err(format!("Decoded {:?} instead of {:?}", item, value))
+    } else if written != expected_len {
+        Err(format!("Encoder promised {} bytes but wrote {}", expected_len, written))
+    } else if written != read {
+        Err(format!("Encoder wrote {} bytes but decoder read {}", written, read))
+    } else {
+        Ok(())
+    }
+}
+
+struct Roundtrip<T, FC, FD> {
+    make_enc: FC,
+    make_dec: FD,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T, C, D, FC, FD> Roundtrip<T, FC, FD>
+    where T: Clone + Debug + PartialEq,
+          FC: Fn(T) -> C,
+          FD: Fn() -> D,
+          C: AsyncEncodeLen,
+          D: AsyncDecode<Item = T>,
+          D::Error: Debug
+{
+    fn check(&self, input: RoundtripInput<T>) -> Result<(), String> {
+        let enc = (self.make_enc)(input.3.clone());
+        let dec = (self.make_dec)();
+        panic::catch_unwind(AssertUnwindSafe(|| roundtrip_once(enc, dec, input)))
+            .unwrap_or_else(|payload| Err(format!("Panicked: {}", panic_message(&*payload))))
+    }
+}
+
+// Extract the message of a panic, if it has one.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg
+    } else {
+        "<no message>"
+    }
+}
+
+impl<T, C, D, FC, FD> Testable for Roundtrip<T, FC, FD>
+    where T: Arbitrary + Debug + PartialEq,
+          FC: Fn(T) -> C + Send + 'static,
+          FD: Fn() -> D + Send + 'static,
+          C: AsyncEncodeLen,
+          D: AsyncDecode<Item = T>,
+          D::Error: Debug
+{
+    fn result<G: Gen>(&self, g: &mut G) -> TestResult {
+        let input: RoundtripInput<T> = Arbitrary::arbitrary(g);
+        let (mut input, mut msg) = match self.check(input.clone()) {
+            Ok(()) => return TestResult::passed(),
+            Err(msg) => (input, msg),
+        };
+
+        // Keep replacing the failing input with a smaller one that still fails.
+        'shrink: loop {
+            for smaller in input.shrink() {
+                if let Err(smaller_msg) = self.check(smaller.clone()) {
+                    input = smaller;
+                    msg = smaller_msg;
+                    continue 'shrink;
+                }
+            }
+            break;
+        }
+
+        let (buf_size, read_ops, write_ops, value) = input;
+        TestResult::error(format!("{}\nvalue: {:?}\nbuffer capacity: {}\nread ops: {:?}\n\
+                                   write ops: {:?}",
+                                  msg,
+                                  value,
+                                  buf_size + 1,
+                                  read_ops,
+                                  write_ops))
+    }
+}
+
+/// Check via quickcheck that arbitrary values survive a round trip through the encoders created
+/// by `make_enc` and the decoders created by `make_dec`.
+///
+/// Each round trip runs the encoder and decoder concurrently over a ring buffer of arbitrary size,
+/// with arbitrary partial reads and writes. It fails if the decoded value differs from the
+/// encoded one, if the encoder writes a different number of bytes than it initially promised via
+/// `AsyncEncodeLen`, or if the decoder reads a different number of bytes than were written.
+///
+/// Panics on failure, reporting a shrunk counterexample including the partial operations.
+pub fn roundtrip_property<T, C, D, FC, FD>(make_enc: FC, make_dec: FD)
+    where T: Arbitrary + Debug + PartialEq,
+          FC: Fn(T) -> C + Send + 'static,
+          FD: Fn() -> D + Send + 'static,
+          C: AsyncEncodeLen,
+          D: AsyncDecode<Item = T>,
+          D::Error: Debug
+{
+    QuickCheck::new().quickcheck(Roundtrip {
+                                     make_enc,
+                                     make_dec,
+                                     _value: PhantomData,
+                                 });
+}
+
+// The outcome of fully decoding some bytes, `None` if the decoder panicked.
+type DecodeOutcome<D> = Option<Result<(<D as AsyncDecode>::Item, usize),
+                                      DecodeError<<D as AsyncDecode>::Error>>>;
+
+fn decode_bytes<D: AsyncDecode>(bytes: &[u8], dec: D) -> DecodeOutcome<D> {
+    panic::catch_unwind(AssertUnwindSafe(|| {
+                                             block_on(decode(bytes, dec))
+                                                 .map(|(_, item, read)| (item, read))
+                                                 .map_err(|(_, err)| err)
+                                         }))
+            .ok()
+}
+
+// Panics with a report listing all problems, if there are any.
+fn report(what: &str, problems: Vec<String>) {
+    if !problems.is_empty() {
+        panic!("{} in {} cases:\n{}",
+               what,
+               problems.len(),
+               problems.join("\n"));
+    }
+}
+
+/// Assert that decoding any proper prefix of the given valid encoding fails with an error of kind
+/// `UnexpectedEof`.
+///
+/// Each prefix is decoded by a fresh decoder created via `make_dec`. Panics with a report of all
+/// prefix lengths for which the decoder panicked, produced an item, or failed with a different
+/// error.
+pub fn assert_truncation_safe<D, F>(bytes: &[u8], make_dec: F)
+    where F: Fn() -> D,
+          D: AsyncDecode,
+          D::Item: Debug,
+          D::Error: Debug
+{
+    let mut problems = Vec::new();
+
+    for len in 0..bytes.len() {
+        match decode_bytes(&bytes[..len], make_dec()) {
+            None => problems.push(format!("{}: panicked", len)),
+            Some(Ok((item, read))) => {
+                problems.push(format!("{}: decoded {:?} from {} bytes", len, item, read))
+            }
+            Some(Err(DecodeError::ReaderError(ref err))) if err.kind() == UnexpectedEof => {}
+            Some(Err(err)) => problems.push(format!("{}: errored with {:?}", len, err)),
+        }
+    }
+
+    report("Truncated input not rejected", problems);
+}
+
+/// Assert that flipping any single bit of the given valid encoding makes decoding it either fail
+/// or yield a different item.
+///
+/// The decoder must consume all of `bytes`. Each modified encoding is decoded by a fresh decoder
+/// created via `make_dec`. Panics with a report of all bit flips that went unnoticed or made the
+/// decoder panic.
+pub fn assert_corruption_detected<D, F>(bytes: &[u8], make_dec: F)
+    where F: Fn() -> D,
+          D: AsyncDecode,
+          D::Item: Debug + PartialEq,
+          D::Error: Debug
+{
+    let expected = match decode_bytes(bytes, make_dec()) {
+        Some(Ok((item, read))) => {
+            assert_eq!(read, bytes.len(), "Decoder did not consume the whole input");
+            item
+        }
+        Some(Err(err)) => panic!("Failed to decode the unmodified input: {:?}", err),
+        None => panic!("Panicked decoding the unmodified input"),
+    };
+
+    let mut problems = Vec::new();
+    let mut corrupted = bytes.to_vec();
+
+    for offset in 0..bytes.len() {
+        for bit in 0..8 {
+            corrupted[offset] ^= 1 << bit;
+            match decode_bytes(&corrupted, make_dec()) {
+                None => problems.push(format!("{} (bit {}): panicked", offset, bit)),
+                Some(Ok((ref item, _))) if *item == expected => {
+                    problems.push(format!("{} (bit {}): decoded the original item", offset, bit))
+                }
+                Some(_) => {}
+            }
+            corrupted[offset] ^= 1 << bit;
+        }
+    }
+
+    report("Corruption not detected", problems);
+}
+
+// Fully encodes into the given writer, recording a problem whenever `remaining_bytes` does not
+// decrease by exactly the number of bytes written.
+fn encode_checked<W, C>(writer: &mut W, enc: C, problems: &mut Vec<String>) -> Result<(), FutIoErr>
+    where W: AsyncWrite,
+          C: AsyncEncodeLen
+{
+    let mut enc = Some(enc);
+    block_on(poll_fn(|cx| -> Result<_, Never> {
+        loop {
+            let current = enc.take().unwrap();
+            let before = current.remaining_bytes();
+
+            match current.poll_encode(cx, writer) {
+                PollEnc::Done(written) => {
+                    if written != before {
+                        problems.push(format!("promised {} bytes but wrote {} and was done",
+                                              before,
+                                              written));
+                    }
+                    return Ok(Ready(Ok(())));
+                }
+                PollEnc::Progress(current, written) => {
+                    let after = current.remaining_bytes();
+                    if after > before || before - after != written {
+                        problems.push(format!("remaining bytes went from {} to {} after writing {}",
+                                              before,
+                                              after,
+                                              written));
+                    }
+                    enc = Some(current);
+                }
+                PollEnc::Pending(_) => {
+                    problems.push("returned Pending from a writer that is never pending"
+                                      .to_string());
+                    return Ok(Ready(Ok(())));
+                }
+                PollEnc::Errored(err) => return Ok(Ready(Err(err))),
+            }
+        }
+    }))
+            .unwrap()
+}
+
+/// Assert that an encoder handles writers of insufficient capacity.
+///
+/// This first encodes into a writer with exactly as much capacity as the encoder claims to need,
+/// and then into writers of every smaller capacity, each time with a fresh encoder created via
+/// `make_enc`. The smaller writers must make the encoder fail with an error of kind `WriteZero`,
+/// after writing a prefix of the full encoding. Throughout, `AsyncEncodeLen::remaining_bytes`
+/// must decrease by exactly the number of bytes written. Panics with a report of all violations.
+pub fn assert_write_capacity_safe<C, F>(make_enc: F)
+    where F: Fn() -> C,
+          C: AsyncEncodeLen
+{
+    let enc = make_enc();
+    let len = enc.remaining_bytes();
+    let mut full = vec![0; len];
+    let mut problems = Vec::new();
+    let mut full_problems = Vec::new();
+
+    {
+        let mut writer = Cursor::new(&mut full[..]);
+        match encode_checked(&mut writer, enc, &mut full_problems) {
+            Ok(()) if writer.position() as usize == len => {}
+            Ok(()) => {
+                full_problems.push(format!("wrote only {} bytes", writer.position()))
+            }
+            Err(err) => full_problems.push(format!("errored with {:?}", err)),
+        }
+    }
+    problems.extend(full_problems.into_iter().map(|p| format!("{} (full): {}", len, p)));
+
+    for capacity in 0..len {
+        let mut buf = vec![0; capacity];
+        let mut cap_problems = Vec::new();
+
+        {
+            let mut writer = Cursor::new(&mut buf[..]);
+            match encode_checked(&mut writer, make_enc(), &mut cap_problems) {
+                Ok(()) => cap_problems.push("did not error".to_string()),
+                Err(ref err) if err.kind() == WriteZero => {}
+                Err(err) => cap_problems.push(format!("errored with {:?}", err)),
+            }
+        }
+        if buf[..] != full[..capacity] {
+            cap_problems.push("wrote bytes differing from the full encoding".to_string());
+        }
+
+        problems.extend(cap_problems.into_iter().map(|p| format!("{}: {}", capacity, p)));
+    }
+
+    report("Insufficient write capacity not handled", problems);
+}
+
+/// A reader that returns `Pending` whenever its script says so, recording that it registered a
+/// wakeup.
+///
+/// The script yields one `bool` per call to `poll_read`, `true` meaning to return `Pending`
+/// instead of reading. Once the script is exhausted, all calls are forwarded to the inner reader.
+/// The wakeup is registered by immediately waking the task, as if new data arrived right away.
+pub struct PendingReader<R, I> {
+    inner: R,
+    script: I,
+    registered: bool,
+}
+
+impl<R, I> PendingReader<R, I> {
+    /// Create a new `PendingReader`, wrapping `inner` and following `script`.
+    pub fn new(inner: R, script: I) -> PendingReader<R, I> {
+        PendingReader {
+            inner,
+            script,
+            registered: false,
+        }
+    }
+
+    /// Return whether this returned `Pending` (and thus registered a wakeup) since the last call
+    /// to this method.
+    pub fn take_registered(&mut self) -> bool {
+        let registered = self.registered;
+        self.registered = false;
+        registered
+    }
+}
+
+impl<R: AsyncRead, I: Iterator<Item = bool>> AsyncRead for PendingReader<R, I> {
+    fn poll_read(&mut self, cx: &mut Context, buf: &mut [u8]) -> Poll<usize, FutIoErr> {
+        if self.script.next() == Some(true) {
+            cx.waker().wake();
+            self.registered = true;
+            Ok(Pending)
+        } else {
+            self.inner.poll_read(cx, buf)
+        }
+    }
+}
+
+/// A writer that returns `Pending` whenever its script says so, recording that it registered a
+/// wakeup.
+///
+/// This behaves like a `PendingReader`, except that the script is advanced by calls to
+/// `poll_write`. Flushing and closing are forwarded to the inner writer.
+pub struct PendingWriter<W, I> {
+    inner: W,
+    script: I,
+    registered: bool,
+}
+
+impl<W, I> PendingWriter<W, I> {
+    /// Create a new `PendingWriter`, wrapping `inner` and following `script`.
+    pub fn new(inner: W, script: I) -> PendingWriter<W, I> {
+        PendingWriter {
+            inner,
+            script,
+            registered: false,
+        }
+    }
+
+    /// Return whether this returned `Pending` (and thus registered a wakeup) since the last call
+    /// to this method.
+    pub fn take_registered(&mut self) -> bool {
+        let registered = self.registered;
+        self.registered = false;
+        registered
+    }
+
+    /// Return the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite, I: Iterator<Item = bool>> AsyncWrite for PendingWriter<W, I> {
+    fn poll_write(&mut self, cx: &mut Context, buf: &[u8]) -> Poll<usize, FutIoErr> {
+        if self.script.next() == Some(true) {
+            cx.waker().wake();
+            self.registered = true;
+            Ok(Pending)
+        } else {
+            self.inner.poll_write(cx, buf)
+        }
+    }
+
+    fn poll_flush(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.inner.poll_flush(cx)
+    }
+
+    fn poll_close(&mut self, cx: &mut Context) -> Poll<(), FutIoErr> {
+        self.inner.poll_close(cx)
+    }
+}
+
+/// A decoder for exactly the given number of bytes, calling `poll_read` as often as it can in
+/// every poll.
+///
+/// Most decoders read at most once per poll. Use this as the inner decoder of a decoder that
+/// presents its own reader, to check that it does not signal the end of the data when it merely
+/// has no more data buffered.
+pub struct GreedyDecoder {
+    buf: Vec<u8>,
+    filled: usize,
+}
+
+impl GreedyDecoder {
+    /// Create a new `GreedyDecoder`, decoding `len` bytes.
+    pub fn new(len: usize) -> GreedyDecoder {
+        GreedyDecoder {
+            buf: vec![0; len],
+            filled: 0,
+        }
+    }
+}
+
+impl AsyncDecode for GreedyDecoder {
+    type Item = Vec<u8>;
+    type Error = Never;
+
+    fn poll_decode<R: AsyncRead>(mut self,
+                                 cx: &mut Context,
+                                 reader: &mut R)
+                                 -> PollDec<Self::Item, Self, Self::Error> {
+        let mut read = 0;
+
+        while self.filled < self.buf.len() {
+            match reader.poll_read(cx, &mut self.buf[self.filled..]) {
+                Ok(Ready(0)) => {
+                    return PollDec::Errored(FutIoErr::new(UnexpectedEof, "greedy").into())
+                }
+                Ok(Ready(n)) => {
+                    self.filled += n;
+                    read += n;
+                }
+                Ok(Pending) if read == 0 => return PollDec::Pending(self),
+                Ok(Pending) => return PollDec::Progress(self, read),
+                Err(err) => return PollDec::Errored(err.into()),
+            }
+        }
+
+        PollDec::Done(self.buf, read)
+    }
+}
+
+// Returns `Pending` before every other read or write.
+fn alternating() -> ::std::iter::Cycle<::std::vec::IntoIter<bool>> {
+    vec![true, false].into_iter().cycle()
+}
+
+/// Assert that the decoders created by `make_dec` never return `Pending` without a wakeup having
+/// been registered, which would deadlock a real executor.
+///
+/// This decodes `bytes` from a `PendingReader` returning `Pending` before every actual read,
+/// failing if the decoder returns `Pending` in a poll in which the reader did not, or if decoding
+/// fails.
+pub fn check_wakeups<D, F>(make_dec: F, bytes: &[u8])
+    where F: Fn() -> D,
+          D: AsyncDecode,
+          D::Error: Debug
+{
+    let mut reader = PendingReader::new(bytes, alternating());
+    let mut dec = Some(make_dec());
+    let mut read = 0;
+
+    let res = block_on(poll_fn(|cx| -> Result<_, Never> {
+        loop {
+            reader.take_registered();
+            match dec.take().unwrap().poll_decode(cx, &mut reader) {
+                PollDec::Done(_, _) => return Ok(Ready(Ok(()))),
+                PollDec::Progress(current, r) => {
+                    read += r;
+                    dec = Some(current);
+                }
+                PollDec::Pending(current) => {
+                    if !reader.take_registered() {
+                        return Ok(Ready(Err(format!("Decoder returned Pending without a \
+                                                     registered wakeup after reading {} bytes",
+                                                    read))));
+                    }
+                    dec = Some(current);
+                    return Ok(Pending);
+                }
+                PollDec::Errored(err) => {
+                    return Ok(Ready(Err(format!("Decoder errored after reading {} bytes: {:?}",
+                                                read,
+                                                err))))
+                }
+            }
+        }
+    }))
+            .unwrap();
+
+    if let Err(msg) = res {
+        panic!("{}", msg);
+    }
+}
+
+/// Assert that the encoders created by `make_enc` never return `Pending` without a wakeup having
+/// been registered, which would deadlock a real executor.
+///
+/// This is the counterpart of `check_wakeups`, encoding into a `PendingWriter` returning `Pending`
+/// before every actual write.
+pub fn check_encode_wakeups<C, F>(make_enc: F)
+    where F: Fn() -> C,
+          C: AsyncEncode
+{
+    let mut writer = PendingWriter::new(Cursor::new(Vec::new()), alternating());
+    let mut enc = Some(make_enc());
+    let mut written = 0;
+
+    let res = block_on(poll_fn(|cx| -> Result<_, Never> {
+        loop {
+            writer.take_registered();
+            match enc.take().unwrap().poll_encode(cx, &mut writer) {
+                PollEnc::Done(_) => return Ok(Ready(Ok(()))),
+                PollEnc::Progress(current, w) => {
+                    written += w;
+                    enc = Some(current);
+                }
+                PollEnc::Pending(current) => {
+                    if !writer.take_registered() {
+                        return Ok(Ready(Err(format!("Encoder returned Pending without a \
+                                                     registered wakeup after writing {} bytes",
+                                                    written))));
+                    }
+                    enc = Some(current);
+                    return Ok(Pending);
+                }
+                PollEnc::Errored(err) => {
+                    return Ok(Ready(Err(format!("Encoder errored after writing {} bytes: {:?}",
+                                                written,
+                                                err))))
+                }
+            }
+        }
+    }))
+            .unwrap();
+
+    if let Err(msg) = res {
+        panic!("{}", msg);
+    }
+}
+
+/// Assert that the given encoder writes exactly the bytes given in hexadecimal, ignoring
+/// whitespace.
+///
+/// Panics with a hexdump of both encodings if they differ.
+pub fn assert_encodes_to<C: AsyncEncode>(enc: C, expected_hex: &str) {
+    let expected = parse_hex(expected_hex);
+    let actual = encode_to_vec(enc);
+    if actual != expected {
+        panic!("Encoding differs from the expected bytes:\n{}",
+               hexdump_diff(("expected", &expected), ("actual", &actual)));
+    }
+}
+
+/// Assert that the given decoder decodes the bytes given in hexadecimal to the `expected` item,
+/// consuming all of them.
+pub fn assert_decodes_from<D>(hex: &str, dec: D, expected: D::Item)
+    where D: AsyncDecode,
+          D::Item: Debug + PartialEq,
+          D::Error: Debug
+{
+    let bytes = parse_hex(hex);
+    match block_on(decode(&bytes[..], dec)) {
+        Ok((_, item, read)) => {
+            assert_eq!(item, expected);
+            assert_eq!(read, bytes.len(), "Decoder did not consume all bytes");
+        }
+        Err((_, err)) => panic!("Decoder errored: {:?}", err),
+    }
+}
+
+/// Checks the encodings of a set of named values against a snapshot file.
+///
+/// If the file does not exist yet (or the `UPDATE_SNAPSHOTS` environment variable is set), `check`
+/// records the encodings in it. Otherwise, it compares them to the recorded ones and panics with
+/// a report of all values whose encoding changed, is new or went missing. The file lists one
+/// value per line, as its name followed by a colon and its encoding in hexadecimal.
+pub struct Snapshot {
+    path: PathBuf,
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Snapshot {
+    /// Create a new `Snapshot`, checked against the file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Snapshot {
+        Snapshot {
+            path: path.as_ref().to_path_buf(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add the encoding produced by `enc` under the given `name`.
+    ///
+    /// Panics if the name contains a colon or a line break, or if it was already added.
+    pub fn add<C: AsyncEncode>(mut self, name: &str, enc: C) -> Snapshot {
+        assert!(!name.contains([':', '\n', '\r']),
+                "Invalid snapshot name {:?}",
+                name);
+        assert!(self.entries.iter().all(|(existing, _)| existing != name),
+                "Duplicate snapshot name {:?}",
+                name);
+
+        let encoded = encode_to_vec(enc);
+        self.entries.push((name.to_string(), encoded));
+        self
+    }
+
+    /// Record the encodings if there is no snapshot yet, or compare them to the recorded ones.
+    pub fn check(self) {
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() || !self.path.exists() {
+            let contents: String = self.entries
+                .iter()
+                .map(|(name, encoded)| format!("{}: {}\n", name, hex_line(encoded)))
+                .collect();
+            fs::write(&self.path, contents).expect("Failed to write snapshot");
+            return;
+        }
+
+        let contents = fs::read_to_string(&self.path).expect("Failed to read snapshot");
+        let mut recorded = Vec::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let colon = line.find(':').expect("Malformed snapshot line");
+            recorded.push((line[..colon].to_string(), parse_hex(&line[colon + 1..])));
+        }
+
+        let mut problems = Vec::new();
+        for (name, encoded) in &self.entries {
+            match recorded.iter().find(|(other, _)| other == name) {
+                None => problems.push(format!("{}: not in the snapshot", name)),
+                Some((_, expected)) if expected != encoded => {
+                    problems.push(format!("{}: encoding changed\n{}",
+                                          name,
+                                          hexdump_diff(("recorded", expected),
+                                                       ("actual", encoded))))
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, _) in &recorded {
+            if self.entries.iter().all(|(other, _)| other != name) {
+                problems.push(format!("{}: missing", name));
+            }
+        }
+
+        report(&format!("Snapshot {} does not match", self.path.display()),
+               problems);
+    }
+}
+
+// Interprets the start of some fuzzer input as a schedule of partial reads, returning it and the
+// remaining input. The first byte is the number of operations, each following byte one operation.
+fn fuzz_schedule(data: &[u8]) -> (Vec<PartialOp>, &[u8]) {
+    match data.split_first() {
+        None => (Vec::new(), data),
+        Some((&len, rest)) => {
+            let len = (len as usize).min(rest.len());
+            let ops = rest[..len]
+                .iter()
+                .map(|&op| match op {
+                         0 => PartialOp::Pending,
+                         1 => PartialOp::Unlimited,
+                         n => PartialOp::Limited(n as usize - 1),
+                     })
+                .collect();
+            (ops, &rest[len..])
+        }
+    }
+}
+
+/// Decode arbitrary fuzzer input with the decoder created by `make_dec`, panicking if the decoder
+/// misbehaves.
+///
+/// The first byte of `data` is the number of following bytes which describe a schedule of
+/// partial and pending reads, the rest is the input to decode. Decoding may fail, but this
+/// panics if the decoder panics, returns `Progress(_, 0)` more than `MAX_IDLE_PROGRESS` times in
+/// a row, or reports a different number of bytes than it actually read.
+///
+/// This does not limit memory usage, run fuzz targets with an allocation limit for that, e.g.
+/// the one of the `fuzz` crate in this repository.
+pub fn fuzz_decoder<D, F>(data: &[u8], make_dec: F)
+    where F: FnOnce() -> D,
+          D: AsyncDecode
+{
+    let (ops, input) = fuzz_schedule(data);
+    let mut reader = Recorder::new(PartialRead::new(input, ops.into_iter()));
+    let mut dec = Some(make_dec());
+    let mut reported = 0;
+    let mut idle = 0;
+
+    block_on(poll_fn(|cx| -> Result<_, Never> {
+        loop {
+            match dec.take().unwrap().poll_decode(cx, &mut reader) {
+                PollDec::Done(_, read) => {
+                    reported += read;
+                    return Ok(Ready(()));
+                }
+                PollDec::Progress(current, read) => {
+                    reported += read;
+                    if read == 0 {
+                        idle += 1;
+                        assert!(idle <= MAX_IDLE_PROGRESS,
+                                "Decoder made no progress in {} consecutive polls",
+                                idle);
+                    } else {
+                        idle = 0;
+                    }
+                    dec = Some(current);
+                }
+                PollDec::Pending(current) => {
+                    dec = Some(current);
+                    return Ok(Pending);
+                }
+                PollDec::Errored(_) => {
+                    // The bytes read in the erroring poll are not reported.
+                    assert!(reported <= reader.bytes.len(),
+                            "Decoder reported {} bytes but only read {}",
+                            reported,
+                            reader.bytes.len());
+                    reported = reader.bytes.len();
+                    return Ok(Ready(()));
+                }
+            }
+        }
+    }))
+            .unwrap();
+
+    assert_eq!(reported,
+               reader.bytes.len(),
+               "Decoder reported a different number of bytes than it read");
+}
+
+#[cfg(test)]
+mod tests {
+    use async_codec::{AsyncDecode, AsyncEncode, AsyncEncodeLen, PollDec, PollEnc};
+    use async_byteorder::{decode_u8, decode_u16_be, encode_u8, encode_u16_be};
+    use futures_core::task::Context;
+    use futures_io::{AsyncRead, AsyncWrite};
+
+    use async_ringbuffer::ring_buffer;
+
+    use super::*;
+    use super::super::{decoder, encoder};
+
+    #[test]
+    fn roundtrip() {
+        roundtrip_property(|(a, b)| encoder::chain(encode_u8(a), encode_u16_be(b)),
+                           || decoder::chain(decode_u8(), decode_u16_be()));
+    }
+
+    // Claims to write one byte more than it does.
+    struct Lying(u8);
+
+    impl AsyncEncode for Lying {
+        fn poll_encode<W: AsyncWrite>(self, cx: &mut Context, writer: &mut W) -> PollEnc<Self> {
+            match encode_u8(self.0).poll_encode(cx, writer) {
+                PollEnc::Done(written) => PollEnc::Done(written),
+                PollEnc::Progress(_, written) => PollEnc::Progress(self, written),
+                PollEnc::Pending(_) => PollEnc::Pending(self),
+                PollEnc::Errored(err) => PollEnc::Errored(err),
+            }
+        }
+    }
+
+    impl AsyncEncodeLen for Lying {
+        fn remaining_bytes(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Encoder promised 2 bytes but wrote 1")]
+    fn inaccurate_len() {
+        roundtrip_property(Lying, decode_u8);
+    }
+
+    #[test]
+    #[should_panic(expected = "Panicked: decoder exploded")]
+    fn roundtrip_panic_message() {
+        roundtrip_property(encode_u8,
+                           || decoder::map(decode_u8(), |_| -> u8 { panic!("decoder exploded") }));
+    }
+
+    #[test]
+    fn write_capacity() {
+        assert_write_capacity_safe(|| encoder::chain(encode_u8(1), encode_u16_be(2)));
+        assert_write_capacity_safe(|| encoder::until_delimiter(b"abc", b"\r\n").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "2 (full): promised 2 bytes but wrote 1 and was done")]
+    fn write_capacity_inaccurate_len() {
+        assert_write_capacity_safe(|| Lying(0));
+    }
+
+    // Returns `Pending` once without reading anything, then decodes a byte.
+    struct Spurious(bool);
+
+    impl AsyncDecode for Spurious {
+        type Item = u8;
+        type Error = ::futures_core::Never;
+
+        fn poll_decode<R: AsyncRead>(self,
+                                     cx: &mut Context,
+                                     reader: &mut R)
+                                     -> PollDec<Self::Item, Self, Self::Error> {
+            if !self.0 {
+                return PollDec::Pending(Spurious(true));
+            }
+
+            match decode_u8().poll_decode(cx, reader) {
+                PollDec::Done(item, read) => PollDec::Done(item, read),
+                PollDec::Progress(_, read) => PollDec::Progress(self, read),
+                PollDec::Pending(_) => PollDec::Pending(self),
+                PollDec::Errored(err) => PollDec::Errored(err),
+            }
+        }
+    }
+
+    #[test]
+    fn wakeups() {
+        check_wakeups(|| decoder::chain(decode_u8(), decode_u16_be()), &[1, 2, 3]);
+        check_encode_wakeups(|| encoder::chain(encode_u8(1), encode_u16_be(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Decoder returned Pending without a registered wakeup")]
+    fn spurious_pending() {
+        check_wakeups(|| Spurious(false), &[1]);
+    }
+
+    #[test]
+    fn report() {
+        let (w, r) = ring_buffer(4);
+        let report = test_codec_len_report(r,
+                                           w,
+                                           decoder::chain(decode_u8(), decode_u16_be()),
+                                           encoder::chain(encode_u8(1), encode_u16_be(2)));
+        assert!(report.is_ok());
+        assert_eq!(report.item, Some((1, 2)));
+
+        // The decoder stops after a single byte.
+        let (w, r) = ring_buffer(4);
+        let report = test_codec_len_report(r, w, decode_u8(), Lying(7));
+        assert!(!report.is_ok());
+        assert_eq!((report.promised, report.written.len(), report.read.len()),
+                   (Some(2), 1, 1));
+
+        let (w, r) = ring_buffer(64);
+        let report = test_codec_report(r,
+                                       w,
+                                       decoder::chain(decode_u8(), decode_u16_be()),
+                                       encoder::until_delimiter(vec![1; 20], b"\n").unwrap());
+        assert!(report.decoder_error.is_none());
+        assert_eq!(report.to_string(),
+                   "Codec test failed\n\
+                    written: 21 bytes\n\
+                    read: 3 bytes\n\
+                    decoded item: (1, 257)\n  \
+                    offset    written                                         | read\n\
+                    ! 00000000  01 01 01 01 01 01 01 01 01 01 01 01 01 01 01 01 | 01 01 01\n\
+                    ! 00000010  01 01 01 01 0a                                  | ");
+    }
+
+    #[test]
+    fn golden() {
+        assert_encodes_to(encoder::chain(encode_u8(1), encode_u16_be(0x0203)), "01 0203");
+        assert_decodes_from("01 02 03",
+                            decoder::chain(decode_u8(), decode_u16_be()),
+                            (1, 0x0203));
+    }
+
+    #[test]
+    #[should_panic(expected = "Encoding differs from the expected bytes")]
+    fn golden_mismatch() {
+        assert_encodes_to(encode_u16_be(0x0203), "0302");
+    }
+
+    #[test]
+    fn snapshot() {
+        let name = format!("async-codec-util-snapshot-{}", ::std::process::id());
+        let path = env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        let snapshot = |b: u16| {
+            Snapshot::new(&path)
+                .add("a", encode_u8(1))
+                .add("b", encode_u16_be(b))
+        };
+
+        snapshot(2).check();
+        snapshot(2).check();
+        let changed = panic::catch_unwind(|| snapshot(3).check());
+        let missing = panic::catch_unwind(|| Snapshot::new(&path).add("a", encode_u8(1)).check());
+        fs::remove_file(&path).unwrap();
+
+        assert!(changed.is_err());
+        assert!(missing.is_err());
+    }
+
+    // Returns `Progress(_, 0)` forever.
+    struct Stuck;
+
+    impl AsyncDecode for Stuck {
+        type Item = ();
+        type Error = ::futures_core::Never;
+
+        fn poll_decode<R: AsyncRead>(self,
+                                     _: &mut Context,
+                                     _: &mut R)
+                                     -> PollDec<Self::Item, Self, Self::Error> {
+            PollDec::Progress(self, 0)
+        }
+    }
+
+    #[test]
+    fn fuzz() {
+        fuzz_decoder(&[3, 0, 1, 3, 1, 2, 3, 4], || decoder::chain(decode_u8(), decode_u16_be()));
+        fuzz_decoder(&[200, 1], || decoder::until_delimiter(b"\n", 4));
+        fuzz_decoder(&[], decode_u8);
+    }
+
+    #[test]
+    #[should_panic(expected = "Decoder made no progress")]
+    fn fuzz_stuck() {
+        fuzz_decoder(&[0], || Stuck);
+    }
+
+    #[test]
+    fn truncation() {
+        assert_truncation_safe(b"ab\n", || decoder::until_delimiter(b"\n", 4));
+        assert_truncation_safe(&[1, 2, 3], || decoder::chain(decode_u8(), decode_u16_be()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Truncated input not rejected in 1 cases:\n3: errored")]
+    fn truncation_data_error() {
+        assert_truncation_safe(b"abc\n", || decoder::until_delimiter(b"\n", 2));
+    }
+
+    #[test]
+    fn corruption() {
+        assert_corruption_detected(&[1, 2, 3], || decoder::chain(decode_u8(), decode_u16_be()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Corruption not detected in 4 cases:\n0 (bit 0): decoded")]
+    fn corruption_undetected() {
+        assert_corruption_detected(&[0xab], || decoder::map(decode_u8(), |byte| byte & 0xf0));
+    }
+}